@@ -0,0 +1,48 @@
+//! Rewriting files in place.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use tempfile::NamedTempFile;
+
+/// How files are rewritten in place.
+#[derive(Debug, Default)]
+pub(crate) struct InPlace {
+    /// The suffix of a copy of the original file, made before replacing it.
+    pub(crate) backup: Option<String>,
+    /// Whether rewritten files keep their modification time.
+    pub(crate) keep_mtime: bool,
+}
+
+impl InPlace {
+    /// Replaces the contents of the file at `path` with `contents`.
+    ///
+    /// The contents are written to a temporary file in the same directory, which
+    /// is then renamed over the file, so readers see either the old or the new
+    /// contents. The permissions of the file are kept, and so is its modification
+    /// time if asked to.
+    pub(crate) fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        // rewrite the target of a symlink instead of replacing the link
+        let path = fs::canonicalize(path)?;
+        let metadata = fs::metadata(&path)?;
+        let dir = path.parent().unwrap_or(Path::new("/"));
+
+        let mut temp = NamedTempFile::new_in(dir)?;
+        temp.write_all(contents)?;
+        let file = temp.as_file();
+        file.set_permissions(metadata.permissions())?;
+        if self.keep_mtime {
+            file.set_modified(metadata.modified()?)?;
+        }
+        file.sync_all()?;
+
+        if let Some(suffix) = &self.backup {
+            let mut backup = path.clone().into_os_string();
+            backup.push(suffix);
+            fs::copy(&path, backup)?;
+        }
+        temp.persist(&path).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
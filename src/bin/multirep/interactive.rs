@@ -0,0 +1,101 @@
+//! Asking which matches to replace.
+
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use multirep::{apply_edits, Diff, Match, MultiReplacer};
+
+const HELP: &str = "\
+y - replace this match
+n - skip this match
+a - replace this match and all later matches of its pattern
+q - quit; don't replace this match or any later one
+";
+
+/// Asks whether to replace each match, like `git add -p` does for hunks.
+pub(crate) struct Prompt<'r, R, W> {
+    rep: &'r MultiReplacer,
+    input: R,
+    output: W,
+    /// The number of unchanged lines shown around a match.
+    context: usize,
+    /// Whether all matches of each pattern are replaced without asking.
+    accept_all: Vec<bool>,
+    quit: bool,
+}
+
+impl<'r, R: BufRead, W: Write> Prompt<'r, R, W> {
+    /// Creates a prompt reading answers from `input` and writing questions to `output`.
+    pub(crate) fn new(rep: &'r MultiReplacer, input: R, output: W, context: usize) -> Self {
+        Self {
+            rep,
+            input,
+            output,
+            context,
+            accept_all: vec![false; rep.patterns_len()],
+            quit: false,
+        }
+    }
+
+    /// Checks whether the user has quit, after which nothing is replaced.
+    pub(crate) fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Returns `text`, read from the file at `path`, with only the accepted
+    /// matches replaced.
+    pub(crate) fn replace(&mut self, path: &Path, text: &str) -> io::Result<String> {
+        let mut edits = Vec::new();
+        for m in self.rep.find_iter(text) {
+            let new = self.rep.replacement_of(&m);
+            if new == m.as_str() {
+                continue;
+            }
+            if self.accept_all[m.pattern()] || self.ask(path, text, &m, &new)? {
+                edits.push((m.range(), new));
+            } else if self.quit {
+                break;
+            }
+        }
+        let edits = edits.iter().map(|(range, new)| (range.clone(), &**new));
+        apply_edits(text, edits).map_err(io::Error::other)
+    }
+
+    /// Shows the change replacing `m` with `new` would make, and asks whether to
+    /// make it.
+    fn ask(&mut self, path: &Path, text: &str, m: &Match<'_>, new: &str) -> io::Result<bool> {
+        let changed = apply_edits(text, [(m.range(), new)]).map_err(io::Error::other)?;
+        let diff = Diff::with_context(text, &changed, self.context);
+        write!(self.output, "{}\n{diff}", path.display())?;
+
+        loop {
+            write!(
+                self.output,
+                "Replace {:?} with {new:?} [y,n,a,q,?]? ",
+                m.as_str()
+            )?;
+            self.output.flush()?;
+            let mut answer = String::new();
+            if self.input.read_line(&mut answer)? == 0 {
+                // out of answers
+                writeln!(self.output)?;
+                self.quit = true;
+                return Ok(false);
+            }
+
+            match answer.trim() {
+                "y" => return Ok(true),
+                "n" => return Ok(false),
+                "a" => {
+                    self.accept_all[m.pattern()] = true;
+                    return Ok(true);
+                }
+                "q" => {
+                    self.quit = true;
+                    return Ok(false);
+                }
+                _ => write!(self.output, "{HELP}")?,
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Replaces multiple patterns at a time in text read from stdin or files.
+
+use std::borrow::Cow;
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+use std::str;
+
+use clap::{ArgGroup, Parser};
+use multirep::{exchange_bytes, Diff, MultiReplacer, Rule, RuleSet};
+
+use crate::in_place::InPlace;
+use crate::interactive::Prompt;
+use crate::walk::{is_binary, Walk};
+
+mod in_place;
+mod interactive;
+mod walk;
+
+/// Replaces multiple patterns at a time, without replacing replaced text again.
+///
+/// The result is written to stdout, or back to the files with `--in-place`.
+/// `--dry-run` shows which files would be rewritten, or how with `--diff`, and
+/// `--interactive` asks which matches to replace.
+/// Rules from a rules file come before the ones given with `-r`, and earlier
+/// rules win over later ones they overlap.
+#[derive(Debug, Parser)]
+#[command(name = "multirep", version)]
+#[command(group(ArgGroup::new("replacement").required(true).multiple(true).args(["rules", "rules_file", "swap"])))]
+#[command(group(ArgGroup::new("files_mode").multiple(true).args(["in_place", "dry_run"])))]
+#[command(group(ArgGroup::new("shows_diff").multiple(true).args(["diff", "interactive"])))]
+struct Args {
+    /// Replaces FROM with TO. FROM ends at the first `=`.
+    #[arg(short = 'r', long = "rule", value_name = "FROM=TO", value_parser = parse_rule)]
+    rules: Vec<(String, String)>,
+
+    /// Loads rules and options from a JSON file, or a TOML file for other extensions.
+    #[arg(short = 'f', long = "rules", value_name = "FILE")]
+    rules_file: Option<PathBuf>,
+
+    /// Exchanges A and B, the longer one winning where they overlap.
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["rules", "rules_file"])]
+    swap: Option<Vec<String>>,
+
+    /// Rewrites the files instead of writing to stdout. Files without matches are
+    /// not touched.
+    #[arg(short = 'i', long)]
+    in_place: bool,
+
+    /// Copies each rewritten file to its name with SUFFIX appended first.
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak",
+        requires = "in_place"
+    )]
+    backup: Option<String>,
+
+    /// Keeps the modification time of rewritten files.
+    #[arg(long, requires = "in_place")]
+    keep_mtime: bool,
+
+    /// Rewrites the files under the given directories, or the current one if
+    /// there are none. Skips `.git` and `target` directories, files ignored by
+    /// `.gitignore`, and binary files.
+    #[arg(short = 'R', long, requires = "files_mode")]
+    recursive: bool,
+
+    /// Only rewrites files whose path relative to the directory matches GLOB.
+    #[arg(long, value_name = "GLOB", requires = "recursive")]
+    include: Vec<String>,
+
+    /// Skips files and directories whose path relative to the directory matches GLOB.
+    #[arg(long, value_name = "GLOB", requires = "recursive")]
+    exclude: Vec<String>,
+
+    /// Lists the files which would be rewritten, without rewriting them.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Prints a unified diff of the changes instead of listing the files, which
+    /// can be applied with `git apply` or `patch -p1`.
+    #[arg(long, requires = "dry_run")]
+    diff: bool,
+
+    /// Asks whether to replace each match when rewriting files in place, showing
+    /// the change it makes as a diff. Answers are read from stdin.
+    #[arg(long, requires = "in_place", conflicts_with = "dry_run")]
+    interactive: bool,
+
+    /// Shows N unchanged lines around changes in diffs.
+    #[arg(
+        short = 'U',
+        long,
+        value_name = "N",
+        default_value_t = 3,
+        requires = "shows_diff"
+    )]
+    unified: usize,
+
+    /// Files to read. Reads stdin if there are none or for `-`.
+    files: Vec<PathBuf>,
+}
+
+fn parse_rule(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((from, to)) => Ok((from.to_owned(), to.to_owned())),
+        None => Err(format!("expected FROM=TO, found `{s}`")),
+    }
+}
+
+/// What to do with each input.
+enum Replace {
+    Rules(MultiReplacer),
+    Swap(String, String),
+}
+
+impl Replace {
+    fn new(args: &Args) -> Result<Self, Box<dyn Error>> {
+        if let Some(swap) = &args.swap {
+            return Ok(Replace::Swap(swap[0].clone(), swap[1].clone()));
+        }
+
+        let mut rules = match &args.rules_file {
+            Some(path) => load_rules(path).map_err(|e| format!("{}: {e}", path.display()))?,
+            None => RuleSet::new(),
+        };
+        rules.extend(args.rules.iter().map(|(from, to)| Rule::literal(from, to)));
+        Ok(Replace::Rules(rules.build()?))
+    }
+
+    fn run(&self, mut reader: impl Read, writer: impl Write) -> io::Result<()> {
+        match self {
+            Replace::Rules(rep) => rep.stream(reader, writer),
+            Replace::Swap(a, b) => {
+                let mut input = Vec::new();
+                reader.read_to_end(&mut input)?;
+                let mut writer = writer;
+                writer.write_all(&exchange_bytes(&input, a.as_bytes(), b.as_bytes()))?;
+                writer.flush()
+            }
+        }
+    }
+
+    /// Returns a replacer doing the same on text.
+    fn replacer(&self) -> Cow<'_, MultiReplacer> {
+        match self {
+            Replace::Rules(rep) => Cow::Borrowed(rep),
+            Replace::Swap(a, b) => Cow::Owned(MultiReplacer::exchange(a, b)),
+        }
+    }
+
+    /// Replaces everything in `input`.
+    fn apply(&self, input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len());
+        self.run(input, &mut output)?;
+        Ok(output)
+    }
+}
+
+fn load_rules(path: &Path) -> Result<RuleSet, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        Ok(serde_json::from_str(&text)?)
+    } else {
+        Ok(toml::from_str(&text)?)
+    }
+}
+
+/// Rewrites each file which has matches, or shows the changes for a dry run.
+fn rewrite(args: &Args, replace: &Replace) -> Result<(), Box<dyn Error>> {
+    let in_place = InPlace {
+        backup: args.backup.clone(),
+        keep_mtime: args.keep_mtime,
+    };
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let replacer = replace.replacer();
+    let mut prompt = args
+        .interactive
+        .then(|| Prompt::new(&replacer, io::stdin().lock(), io::stdout(), args.unified));
+    let mut rewrite_file = |path: &Path, skip_binary: bool| {
+        if prompt.as_ref().is_some_and(|prompt| prompt.has_quit()) {
+            return Ok(());
+        }
+        let input = fs::read(path)?;
+        if skip_binary && is_binary(&input) {
+            eprintln!("multirep: {}: skipping binary file", path.display());
+            return Ok(());
+        }
+        let output = match &mut prompt {
+            Some(prompt) => match str::from_utf8(&input) {
+                Ok(text) => prompt.replace(path, text)?.into_bytes(),
+                Err(_) => {
+                    eprintln!("multirep: {}: skipping invalid UTF-8", path.display());
+                    return Ok(());
+                }
+            },
+            None => replace.apply(&input)?,
+        };
+        if output == input {
+            return Ok(());
+        }
+        match (args.dry_run, args.diff) {
+            (false, _) => in_place.write(path, &output),
+            (true, false) => writeln!(out, "{}", path.display()),
+            (true, true) => write_diff(&mut out, path, &input, &output, args.unified),
+        }
+    };
+
+    if !args.recursive {
+        if args.files.is_empty() || args.files.iter().any(|path| path.as_os_str() == "-") {
+            return Err("can't rewrite stdin in place".into());
+        }
+        for path in &args.files {
+            rewrite_file(path, false).map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    let walk = Walk::new(&args.include, &args.exclude)?;
+    let current = [PathBuf::from(".")];
+    let roots = if args.files.is_empty() {
+        &current[..]
+    } else {
+        &args.files
+    };
+    for root in roots {
+        for path in walk.files(root) {
+            let path = path?;
+            rewrite_file(&path, true).map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes the changes to the file at `path` as a unified diff, with the `a/` and
+/// `b/` prefixes of `git diff`.
+///
+/// Paths are relative to the current directory, or to the root for absolute
+/// paths outside of it, since `git apply` rejects absolute ones.
+fn write_diff(
+    out: &mut impl Write,
+    path: &Path,
+    input: &[u8],
+    output: &[u8],
+    context: usize,
+) -> io::Result<()> {
+    let cwd = env::current_dir()?;
+    let path = path.strip_prefix(&cwd).unwrap_or(path);
+    // `./` would be taken as part of the name when applying the diff
+    let name: PathBuf = path
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_) | Component::ParentDir))
+        .collect();
+    let name = name.display();
+    match (str::from_utf8(input), str::from_utf8(output)) {
+        (Ok(input), Ok(output)) => {
+            let diff = Diff::with_context(input, output, context);
+            write!(out, "--- a/{name}\n+++ b/{name}\n{diff}")
+        }
+        _ => writeln!(out, "Binary files a/{name} and b/{name} differ"),
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let replace = Replace::new(args)?;
+    if args.in_place || args.dry_run {
+        return rewrite(args, &replace);
+    }
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    if args.files.is_empty() {
+        replace.run(io::stdin().lock(), &mut out)?;
+    }
+    for path in &args.files {
+        let result = if path.as_os_str() == "-" {
+            replace.run(io::stdin().lock(), &mut out)
+        } else {
+            File::open(path).and_then(|file| replace.run(file, &mut out))
+        };
+        result.map_err(|e| -> Box<dyn Error> {
+            match e.kind() {
+                ErrorKind::BrokenPipe => e.into(),
+                _ => format!("{}: {e}", path.display()).into(),
+            }
+        })?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if is_broken_pipe(e.as_ref()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("multirep: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn is_broken_pipe(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == ErrorKind::BrokenPipe)
+}
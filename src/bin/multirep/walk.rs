@@ -0,0 +1,88 @@
+//! Finding the files to rewrite under directories.
+
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+
+/// How many bytes at the start of a file are checked for NUL bytes, as in git.
+const BINARY_CHECK_LEN: usize = 8000;
+
+/// Walks directories like git would see them.
+///
+/// Hidden files are visited, but `.git` and `target` directories are skipped,
+/// as is anything ignored by `.gitignore` or `.ignore` files.
+#[derive(Debug)]
+pub(crate) struct Walk {
+    /// Only files matching one of these are visited, if there are any.
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl Walk {
+    pub(crate) fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(glob_set(include)?)
+        };
+        Ok(Self {
+            include,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    /// Finds the files under `root`, or `root` itself if it is a file.
+    ///
+    /// Globs are matched against paths relative to `root`.
+    pub(crate) fn files<'a>(
+        &'a self,
+        root: &'a Path,
+    ) -> impl Iterator<Item = Result<PathBuf, ignore::Error>> + 'a {
+        // the filter has to own what it uses
+        let exclude = self.exclude.clone();
+        let owned_root = root.to_owned();
+
+        WalkBuilder::new(root)
+            .hidden(false)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                let name = entry.file_name();
+                entry.depth() == 0
+                    || !(name == ".git"
+                        || (is_dir && name == "target")
+                        || exclude.is_match(relative(entry.path(), &owned_root)))
+            })
+            .build()
+            .filter_map(move |entry| match entry {
+                Ok(entry) => {
+                    let is_file = entry.file_type().is_some_and(|t| t.is_file());
+                    let included = entry.depth() == 0
+                        || self
+                            .include
+                            .as_ref()
+                            .is_none_or(|include| include.is_match(relative(entry.path(), root)));
+                    (is_file && included).then(|| Ok(entry.into_path()))
+                }
+                Err(e) => Some(Err(e)),
+            })
+    }
+}
+
+fn relative<'p>(path: &'p Path, root: &Path) -> &'p Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    builder.build()
+}
+
+/// Guesses whether `contents` are binary by looking for a NUL byte near the start.
+pub(crate) fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
@@ -0,0 +1,97 @@
+use std::borrow::Cow;
+
+/// The casing of a matched text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    /// There are no cased chars.
+    Uncased,
+    Lower,
+    Upper,
+    /// The first cased char is uppercase and the rest are lowercase.
+    Title,
+    Mixed,
+}
+
+fn is_cased(c: char) -> bool {
+    c.is_lowercase() || c.is_uppercase()
+}
+
+fn shape(s: &str) -> Shape {
+    let mut cased = s.chars().filter(|&c| is_cased(c)).map(char::is_uppercase);
+
+    let Some(first) = cased.next() else {
+        return Shape::Uncased;
+    };
+    let (mut upper, mut lower) = (0, 0);
+    for is_upper in cased {
+        if is_upper {
+            upper += 1;
+        } else {
+            lower += 1;
+        }
+    }
+
+    match (first, upper, lower) {
+        (false, 0, _) => Shape::Lower,
+        // a single uppercase char like `H` is more likely the start of a word
+        (true, 0, _) => Shape::Title,
+        (true, _, 0) => Shape::Upper,
+        _ => Shape::Mixed,
+    }
+}
+
+/// Changes the case of `new` to follow the case of `matched`.
+///
+/// Lowercase, uppercase and titlecase texts are projected as a whole. Otherwise
+/// each char of `new` takes the case of the char of `matched` at the same index,
+/// or of the last char of `matched` if `new` is longer.
+pub(crate) fn preserve_case<'a>(matched: &str, new: &'a str) -> Cow<'a, str> {
+    match shape(matched) {
+        Shape::Uncased => Cow::Borrowed(new),
+        Shape::Lower => Cow::Owned(new.to_lowercase()),
+        Shape::Upper => Cow::Owned(new.to_uppercase()),
+        Shape::Title => {
+            let mut result = String::with_capacity(new.len());
+            let mut first = true;
+            for c in new.chars() {
+                if first && is_cased(c) {
+                    result.extend(c.to_uppercase());
+                    first = false;
+                } else {
+                    result.extend(c.to_lowercase());
+                }
+            }
+            Cow::Owned(result)
+        }
+        Shape::Mixed => {
+            let template: Vec<_> = matched.chars().collect();
+            let mut result = String::with_capacity(new.len());
+            for (i, c) in new.chars().enumerate() {
+                match template.get(i).or(template.last()) {
+                    Some(t) if t.is_uppercase() => result.extend(c.to_uppercase()),
+                    Some(t) if t.is_lowercase() => result.extend(c.to_lowercase()),
+                    _ => result.push(c),
+                }
+            }
+            Cow::Owned(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shapes() {
+        assert_eq!("minami", preserve_case("hana", "Minami"));
+        assert_eq!("MINAMI", preserve_case("HANA", "minami"));
+        assert_eq!("Minami", preserve_case("Hana", "minami"));
+        assert_eq!("Minami", preserve_case("H", "minami"));
+        assert_eq!("MiNami", preserve_case("HaNa", "minami"));
+        assert_eq!("'Minami", preserve_case("Hana", "'minami"));
+        assert_eq!("KAWAII-CHAN", preserve_case("CUTE", "kawaii-chan"));
+        assert_eq!("Minami", preserve_case("123", "Minami"));
+        assert_eq!("ÉCOLE", preserve_case("SCHULE", "école"));
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt;
+use std::ops::Range;
+
+/// The default number of unchanged lines shown around changes, as in `diff -u`.
+const DEFAULT_CONTEXT: usize = 3;
+
+/// A line of a [`Hunk`], including its line terminator if it has one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffLine<'a> {
+    /// A line found in both texts.
+    Context(&'a str),
+    /// A line only found in the original text.
+    Removed(&'a str),
+    /// A line only found in the replaced text.
+    Added(&'a str),
+}
+
+impl DiffLine<'_> {
+    fn parts(&self) -> (char, &str) {
+        match *self {
+            DiffLine::Context(line) => (' ', line),
+            DiffLine::Removed(line) => ('-', line),
+            DiffLine::Added(line) => ('+', line),
+        }
+    }
+}
+
+/// A group of nearby changed lines together with the unchanged lines around them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    old: Range<usize>,
+    new: Range<usize>,
+    lines: Vec<DiffLine<'a>>,
+}
+
+impl<'a> Hunk<'a> {
+    /// Returns the range of lines of the original text covered by this hunk,
+    /// counted from 0.
+    pub fn old_lines(&self) -> Range<usize> {
+        self.old.clone()
+    }
+
+    /// Returns the range of lines of the replaced text covered by this hunk,
+    /// counted from 0.
+    pub fn new_lines(&self) -> Range<usize> {
+        self.new.clone()
+    }
+
+    /// Returns the lines of this hunk in order.
+    pub fn lines(&self) -> &[DiffLine<'a>] {
+        &self.lines
+    }
+}
+
+/// Formats the hunk in unified diff format.
+impl fmt::Display for Hunk<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // an empty range is shown as starting at the line before it
+        let range = |r: &Range<usize>| match r.len() {
+            0 => format!("{},0", r.start),
+            1 => format!("{}", r.start + 1),
+            len => format!("{},{len}", r.start + 1),
+        };
+        writeln!(f, "@@ -{} +{} @@", range(&self.old), range(&self.new))?;
+
+        for line in &self.lines {
+            let (prefix, text) = line.parts();
+            match text.strip_suffix('\n') {
+                Some(text) => writeln!(f, "{prefix}{text}")?,
+                None => write!(f, "{prefix}{text}\n\\ No newline at end of file\n")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The differences between the lines of two texts.
+///
+/// Created by [`diff`] or [`Diff::with_context`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diff<'a> {
+    hunks: Vec<Hunk<'a>>,
+}
+
+impl<'a> Diff<'a> {
+    /// Compares `original` and `replaced` line by line, showing `context`
+    /// unchanged lines around changes.
+    ///
+    /// ```
+    /// use multirep::Diff;
+    ///
+    /// let diff = Diff::with_context("a\nb\nc\nd\n", "a\nB\nc\nd\n", 1);
+    /// assert_eq!("@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n", diff.to_string());
+    /// ```
+    pub fn with_context(original: &'a str, replaced: &'a str, context: usize) -> Self {
+        let old: Vec<_> = original.split_inclusive('\n').collect();
+        let new: Vec<_> = replaced.split_inclusive('\n').collect();
+        let lines = diff_lines(&old, &new);
+
+        Self {
+            hunks: group(&lines, context),
+        }
+    }
+
+    /// Returns the hunks in order.
+    pub fn hunks(&self) -> &[Hunk<'a>] {
+        &self.hunks
+    }
+
+    /// Checks whether the texts are the same.
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+}
+
+/// Formats the hunks in unified diff format, without file headers.
+impl fmt::Display for Diff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.hunks.iter().try_for_each(|hunk| write!(f, "{hunk}"))
+    }
+}
+
+/// Compares the lines of `original` and `replaced`, as `diff -u` does.
+///
+/// Up to 3 unchanged lines are shown around changes; see [`Diff::with_context`]
+/// to change that. The [`Display`](fmt::Display) output only lacks file headers to be
+/// a unified diff.
+///
+/// ```
+/// use multirep::{diff, multi_replace};
+///
+/// let s = "Hana is cute\nSora is cute\n";
+/// let r = multi_replace(s, &[("Hana", "Minami")]);
+/// let d = diff(s, &r);
+/// assert_eq!(1, d.hunks().len());
+/// assert_eq!(
+///     "@@ -1,2 +1,2 @@\n-Hana is cute\n+Minami is cute\n Sora is cute\n",
+///     d.to_string()
+/// );
+/// ```
+pub fn diff<'a>(original: &'a str, replaced: &'a str) -> Diff<'a> {
+    Diff::with_context(original, replaced, DEFAULT_CONTEXT)
+}
+
+/// Finds a shortest edit script from `old` to `new` with the linear space
+/// variant of Myers' algorithm.
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    diff_into(old, new, &mut lines);
+    // show removed lines before the lines replacing them, as `diff` does
+    for run in lines.split_mut(|line| matches!(line, DiffLine::Context(_))) {
+        run.sort_by_key(|line| matches!(line, DiffLine::Added(_)));
+    }
+    lines
+}
+
+fn diff_into<'a>(old: &[&'a str], new: &[&'a str], lines: &mut Vec<DiffLine<'a>>) {
+    // lines around the changes are usually the same, and cheap to skip
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    lines.extend(old[..prefix].iter().map(|&l| DiffLine::Context(l)));
+    if a.is_empty() {
+        lines.extend(b.iter().map(|&l| DiffLine::Added(l)));
+    } else if b.is_empty() {
+        lines.extend(a.iter().map(|&l| DiffLine::Removed(l)));
+    } else {
+        // both halves around the middle snake need about half as many edits
+        let (x, y, u, v) = middle_snake(a, b);
+        diff_into(&a[..x], &b[..y], lines);
+        lines.extend(a[x..u].iter().map(|&l| DiffLine::Context(l)));
+        diff_into(&a[u..], &b[v..], lines);
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|&l| DiffLine::Context(l)),
+    );
+}
+
+/// Finds the snake `(x, y)..(u, v)` in the middle of a shortest edit script
+/// from `a` to `b`, by searching from both ends until the paths meet.
+///
+/// Both `a` and `b` must be non-empty.
+fn middle_snake(a: &[&str], b: &[&str]) -> (usize, usize, usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    // `forward[offset + k]` is the furthest `x` reached from the start on diagonal
+    // `k = x - y`, and `backward[offset + k]` the furthest one from the end, on
+    // the reversed texts
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = forward.clone();
+    let i = |k: isize| (offset + k) as usize;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[i(k - 1)] < forward[i(k + 1)]) {
+                forward[i(k + 1)]
+            } else {
+                forward[i(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[i(k)] = x;
+            // the backward paths have taken `d - 1` steps
+            let back = delta - k;
+            if odd && (1 - d..d).contains(&back) && x + backward[i(back)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[i(k - 1)] < backward[i(k + 1)]) {
+                backward[i(k + 1)]
+            } else {
+                backward[i(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            let mut y = y0;
+            while x < n && y < m && a[(n - 1 - x) as usize] == b[(m - 1 - y) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[i(k)] = x;
+            let front = delta - k;
+            if !odd && (-d..=d).contains(&front) && forward[i(front)] + x >= n {
+                return (
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                );
+            }
+        }
+    }
+    unreachable!("the paths meet after at most (n + m) / 2 steps each")
+}
+
+/// Groups changed lines with at most `2 * context` unchanged lines between them
+/// into hunks.
+fn group<'a>(lines: &[DiffLine<'a>], context: usize) -> Vec<Hunk<'a>> {
+    // the line numbers before each line of the script
+    let mut positions = Vec::with_capacity(lines.len() + 1);
+    let (mut old, mut new) = (0, 0);
+    for line in lines {
+        positions.push((old, new));
+        match line {
+            DiffLine::Context(_) => (old, new) = (old + 1, new + 1),
+            DiffLine::Removed(_) => old += 1,
+            DiffLine::Added(_) => new += 1,
+        }
+    }
+    positions.push((old, new));
+
+    let changes: Vec<_> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Context(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    let mut hunks = Vec::new();
+    let mut rest = &changes[..];
+    while let Some(&first) = rest.first() {
+        // extend the hunk while the next change is close enough
+        let mut last = first;
+        rest = &rest[1..];
+        while let Some(&next) = rest.first() {
+            if next - last - 1 > context.saturating_mul(2) {
+                break;
+            }
+            last = next;
+            rest = &rest[1..];
+        }
+
+        let start = first.saturating_sub(context);
+        let end = (last + 1).saturating_add(context).min(lines.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        hunks.push(Hunk {
+            old: old_start..old_end,
+            new: new_start..new_end,
+            lines: lines[start..end].to_vec(),
+        });
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn script(old: &str, new: &str) -> String {
+        let old: Vec<_> = old.split_inclusive('\n').collect();
+        let new: Vec<_> = new.split_inclusive('\n').collect();
+        diff_lines(&old, &new)
+            .iter()
+            .map(|line| {
+                let (prefix, text) = line.parts();
+                format!("{prefix}{}", text.trim_end())
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn shortest_script() {
+        assert_eq!("", script("", ""));
+        assert_eq!(" a  b", script("a\nb\n", "a\nb\n"));
+        assert_eq!("-a +b", script("a\n", "b\n"));
+        assert_eq!("+a +b", script("", "a\nb\n"));
+        assert_eq!(
+            "-a +c  b -c  a  b -b  a +c",
+            script("a\nb\nc\na\nb\nb\na\n", "c\nb\na\nb\na\nc\n")
+        );
+        // a line without its newline differs from the same line with it
+        assert_eq!(" a -b +b", script("a\nb", "a\nb\n"));
+    }
+
+    #[test]
+    fn many_changes() {
+        let old: String = (0..3000).map(|i| format!("Hana {i}\n")).collect();
+        let new = old.replace("Hana", "Minami");
+        let d = diff(&old, &new);
+        assert_eq!(1, d.hunks().len());
+        assert_eq!(6000, d.hunks()[0].lines().len());
+
+        // every other line changes
+        let new: String = (0..3000)
+            .map(|i| match i % 2 {
+                0 => format!("Hana {i}\n"),
+                _ => format!("Minami {i}\n"),
+            })
+            .collect();
+        let d = Diff::with_context(&old, &new, 0);
+        assert_eq!(1500, d.hunks().len());
+        assert_eq!(
+            "@@ -2 +2 @@\n-Hana 1\n+Minami 1\n",
+            d.hunks()[0].to_string()
+        );
+    }
+
+    #[test]
+    fn hunks() {
+        let old: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let new: String = (1..=20)
+            .filter(|&i| i != 18)
+            .map(|i| match i {
+                3 => "three\n".to_string(),
+                9 => "nine\n".to_string(),
+                i => format!("{i}\n"),
+            })
+            .collect();
+
+        let d = Diff::with_context(&old, &new, 3);
+        assert_eq!(2, d.hunks().len());
+        assert_eq!(0..12, d.hunks()[0].old_lines());
+        assert_eq!(14..20, d.hunks()[1].old_lines());
+        assert_eq!(14..19, d.hunks()[1].new_lines());
+        assert_eq!(
+            "@@ -15,6 +15,5 @@\n 15\n 16\n 17\n-18\n 19\n 20\n",
+            d.hunks()[1].to_string()
+        );
+
+        assert_eq!(3, Diff::with_context(&old, &new, 2).hunks().len());
+        assert_eq!(1, Diff::with_context(&old, &new, 5).hunks().len());
+        assert!(diff(&old, &old).is_empty());
+        assert_eq!("@@ -0,0 +1 @@\n+a\n", diff("", "a\n").to_string());
+        assert_eq!(
+            "@@ -1 +1 @@\n-a\n\\ No newline at end of file\n+b\n\\ No newline at end of file\n",
+            diff("a", "b").to_string()
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use std::ops::Range;
+
+use crate::error::MultirepError;
+
+/// Replaces byte ranges of `s` with new text, such as edits computed by a linter.
+///
+/// Edits may be given in any order. An empty range inserts its text. Two edits
+/// overlap if they share a byte or start at the same position, since the order
+/// of their texts would be ambiguous.
+///
+/// ```
+/// use multirep::apply_edits;
+///
+/// let s = "Hana is cute";
+/// let r = apply_edits(s, [(8..12, "kawaii"), (0..4, "Minami"), (12..12, "!")]);
+/// assert_eq!("Minami is kawaii!", r.unwrap());
+/// assert!(apply_edits(s, [(0..4, "Minami"), (2..6, "X")]).is_err());
+/// assert!(apply_edits("é", [(1..2, "e")]).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns [`MultirepError::InvalidEdit`] if a range is reversed, out of bounds or
+/// not on char boundaries, and [`MultirepError::OverlappingEdits`] if two edits
+/// overlap.
+pub fn apply_edits<'a, I>(s: &str, edits: I) -> Result<String, MultirepError>
+where
+    I: IntoIterator<Item = (Range<usize>, &'a str)>,
+{
+    let mut edits: Vec<_> = edits.into_iter().enumerate().collect();
+    for (index, (range, _)) in &edits {
+        // `is_char_boundary` is false past the end
+        if range.start > range.end
+            || !s.is_char_boundary(range.start)
+            || !s.is_char_boundary(range.end)
+        {
+            return Err(MultirepError::InvalidEdit {
+                index: *index,
+                range: range.clone(),
+            });
+        }
+    }
+
+    edits.sort_by_key(|(_, (range, _))| (range.start, range.end));
+    for pair in edits.windows(2) {
+        let (a, (prev, _)) = &pair[0];
+        let (b, (next, _)) = &pair[1];
+        if next.start < prev.end || next.start == prev.start {
+            return Err(MultirepError::OverlappingEdits {
+                first: *a.min(b),
+                second: *a.max(b),
+            });
+        }
+    }
+
+    let len = edits.iter().fold(s.len(), |len, (_, (range, new))| {
+        len - range.len() + new.len()
+    });
+    let mut result = String::with_capacity(len);
+    splice_str(&mut result, s, edits.into_iter().map(|(_, edit)| edit));
+    Ok(result)
+}
+
+/// Appends `s` to `out` with each range of `edits` replaced by its text.
+///
+/// `edits` must be ordered by position, must not overlap, and must start and end
+/// on char boundaries.
+pub(crate) fn splice_str<T: AsRef<str>>(
+    out: &mut String,
+    s: &str,
+    edits: impl IntoIterator<Item = (Range<usize>, T)>,
+) {
+    let mut end = 0;
+
+    for (range, new) in edits {
+        out.push_str(&s[end..range.start]);
+        out.push_str(new.as_ref());
+        end = range.end;
+    }
+    out.push_str(&s[end..]);
+}
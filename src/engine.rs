@@ -0,0 +1,491 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use aho_corasick::AhoCorasick;
+
+use crate::error::MultirepError;
+#[cfg(feature = "regex")]
+use crate::fold::fold_ascii_regex;
+use crate::fold::{fold_char, Folded};
+use crate::rule::{Context, Pattern, Rule};
+use crate::word::{WordBoundary, Words};
+
+/// Decides which match wins when matches of different patterns overlap.
+///
+/// ```
+/// use multirep::{MatchKind, MultiReplacer};
+///
+/// let pats = [("Hina", "Minami"), ("Hinata", "Hana")];
+/// let replace = |kind| {
+///     MultiReplacer::builder()
+///         .match_kind(kind)
+///         .build(&pats)
+///         .replace("Hinata")
+/// };
+/// assert_eq!("Minamita", replace(MatchKind::Priority));
+/// assert_eq!("Minamita", replace(MatchKind::LeftmostFirst));
+/// assert_eq!("Hana", replace(MatchKind::LeftmostLongest));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum MatchKind {
+    /// A match of an earlier pattern always wins, wherever it is.
+    ///
+    /// This is what [`multi_replace`](crate::multi_replace) uses.
+    #[default]
+    Priority,
+    /// The match starting first wins. If several matches start at the same
+    /// position, the earlier pattern wins.
+    LeftmostFirst,
+    /// The match starting first wins. If several matches start at the same
+    /// position, the longest one wins.
+    LeftmostLongest,
+    /// The match ending last wins. If several matches end at the same
+    /// position, the earlier pattern wins.
+    Rightmost,
+}
+
+/// Decides whether patterns match text with different case.
+///
+/// ```
+/// use multirep::{CaseSensitivity, MultiReplacer};
+///
+/// let replace = |case, s| {
+///     MultiReplacer::builder()
+///         .case_sensitivity(case)
+///         .build(&[("hana", "Minami"), ("ß", "ss")])
+///         .replace(s)
+/// };
+/// assert_eq!("HANA ẞ", replace(CaseSensitivity::Sensitive, "HANA ẞ"));
+/// assert_eq!("Minami ẞ", replace(CaseSensitivity::Ascii, "HANA ẞ"));
+/// assert_eq!("Minami ss", replace(CaseSensitivity::Unicode, "HANA ẞ"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum CaseSensitivity {
+    /// Patterns only match the exact same bytes.
+    #[default]
+    Sensitive,
+    /// ASCII letters match regardless of case, other chars only match exactly.
+    Ascii,
+    /// Chars match if they are equal under Unicode simple case folding, which maps
+    /// each char to exactly one char (so `ß` doesn't match `ss`).
+    ///
+    /// Folding may change the length of a char, as in `K` (Kelvin sign) and `k`;
+    /// matches are always reported in positions of the original text.
+    Unicode,
+}
+
+/// Options shared by all kinds of replacers.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Options {
+    pub(crate) kind: MatchKind,
+    pub(crate) case: CaseSensitivity,
+    pub(crate) preserve_case: bool,
+    pub(crate) word: WordBoundary,
+}
+
+/// Finds non-overlapping matches of a set of patterns, shared by the `str` and
+/// the byte APIs.
+///
+/// Patterns are numbered by the rule they come from. Literal patterns are searched
+/// with a single automaton and regex patterns one by one.
+#[derive(Debug, Clone)]
+pub(crate) struct Engine {
+    searcher: AhoCorasick,
+    /// The rule of each pattern of `searcher`.
+    literals: Vec<usize>,
+    /// The regex rules, ordered by rule.
+    #[cfg(feature = "regex")]
+    regexes: Vec<(usize, regex::bytes::Regex)>,
+    /// The context constraints of each rule, empty if none has any.
+    contexts: Vec<Vec<Context>>,
+    options: Options,
+    max_pattern_len: usize,
+    context_len: usize,
+}
+
+impl Engine {
+    pub(crate) fn new<I, P>(pats: I, options: &Options) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        Self::with_literals(pats.into_iter().enumerate(), options)
+    }
+
+    /// Compiles literal and regex rules, failing if a regex is invalid.
+    pub(crate) fn from_rules(rules: &[Rule], options: &Options) -> Result<Self, MultirepError> {
+        let mut literals = Vec::new();
+        #[cfg(feature = "regex")]
+        let mut regexes = Vec::new();
+
+        for (i, rule) in rules.iter().enumerate() {
+            match rule.pattern() {
+                Pattern::Literal(pat) => literals.push((i, pat.as_bytes())),
+                #[cfg(feature = "regex")]
+                Pattern::Regex(pat) => {
+                    // like for literals, only ASCII letters are folded, which the
+                    // `i` flag of the regex crate cannot do
+                    let folded = match options.case {
+                        CaseSensitivity::Ascii => fold_ascii_regex(pat),
+                        _ => None,
+                    };
+                    let re = regex::bytes::RegexBuilder::new(folded.as_deref().unwrap_or(pat))
+                        .case_insensitive(options.case == CaseSensitivity::Unicode)
+                        .build()
+                        .map_err(|source| MultirepError::Regex { index: i, source })?;
+                    regexes.push((i, re));
+                }
+            }
+        }
+
+        let mut engine = Self::with_literals(literals, options);
+        if rules.iter().any(|rule| !rule.contexts().is_empty()) {
+            engine.contexts = rules.iter().map(|rule| rule.contexts().to_vec()).collect();
+            let longest = engine
+                .contexts
+                .iter()
+                .flatten()
+                .map(|ctx| match options.case {
+                    // a folded char matches an original char of at most 4 bytes
+                    CaseSensitivity::Unicode => ctx.text.chars().count() * 4,
+                    _ => ctx.text.len(),
+                });
+            engine.context_len = longest.fold(engine.context_len, usize::max);
+        }
+        #[cfg(feature = "regex")]
+        if !regexes.is_empty() {
+            // a regex may match any number of bytes
+            engine.max_pattern_len = usize::MAX;
+            engine.regexes = regexes;
+        }
+        Ok(engine)
+    }
+
+    /// Builds an engine for literal patterns given with the rule they come from.
+    fn with_literals<I, P>(pats: I, options: &Options) -> Self
+    where
+        I: IntoIterator<Item = (usize, P)>,
+        P: AsRef<[u8]>,
+    {
+        let (literals, pats): (Vec<_>, Vec<_>) = pats.into_iter().unzip();
+        let mut builder = AhoCorasick::builder();
+        builder.ascii_case_insensitive(options.case == CaseSensitivity::Ascii);
+
+        let (searcher, max_pattern_len) = if options.case == CaseSensitivity::Unicode {
+            let pats: Vec<_> = pats.iter().map(|pat| Folded::new(pat.as_ref())).collect();
+            // a folded char matches an original char of at most 4 bytes
+            let max_chars = pats.iter().map(Folded::chars).max().unwrap_or(0);
+            (
+                builder.build(pats.iter().map(|pat| &pat.text)),
+                max_chars * 4,
+            )
+        } else {
+            let searcher = builder.build(pats);
+            let max_pattern_len = searcher.as_ref().map_or(0, |s| s.max_pattern_len());
+            (searcher, max_pattern_len)
+        };
+
+        Self {
+            searcher: searcher.expect("failed to build multi-pattern automaton"),
+            literals,
+            #[cfg(feature = "regex")]
+            regexes: Vec::new(),
+            contexts: Vec::new(),
+            options: options.clone(),
+            max_pattern_len,
+            context_len: options.word.context_len(),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> MatchKind {
+        self.options.kind
+    }
+
+    pub(crate) fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Returns the maximum length of an occurrence of any pattern in the haystack.
+    pub(crate) fn max_pattern_len(&self) -> usize {
+        self.max_pattern_len
+    }
+
+    /// Returns how many bytes before and after an occurrence are needed to decide
+    /// whether it can be a match.
+    pub(crate) fn context_len(&self) -> usize {
+        self.context_len
+    }
+
+    /// Finds the accepted matches in `haystack` as `(position, length, pattern)`,
+    /// ordered by position.
+    ///
+    /// If `utf8` is set, empty matches which are not on char boundaries are ignored.
+    pub(crate) fn find(&self, haystack: &[u8], utf8: bool) -> Vec<(usize, usize, usize)> {
+        self.resolve(self.candidates(haystack, utf8))
+    }
+
+    /// Picks the non-overlapping winners among `candidates`, ordered by position.
+    pub(crate) fn resolve(
+        &self,
+        mut candidates: Vec<(usize, usize, usize)>,
+    ) -> Vec<(usize, usize, usize)> {
+        // the order in which candidates are considered decides which one wins
+        match self.options.kind {
+            MatchKind::Priority => candidates = self_greedy(candidates),
+            MatchKind::LeftmostFirst => candidates.sort_unstable_by_key(|&(i, _, pat)| (i, pat)),
+            MatchKind::LeftmostLongest => {
+                candidates.sort_unstable_by_key(|&(i, len, pat)| (i, Reverse(len), pat))
+            }
+            MatchKind::Rightmost => {
+                candidates.sort_unstable_by_key(|&(i, len, pat)| (Reverse(i + len), pat))
+            }
+        }
+
+        let mut indices = BTreeMap::new();
+        for (i, len, pat) in candidates {
+            if !overlaps(&indices, i, len) {
+                indices.insert(i, (len, pat));
+            }
+        }
+
+        indices
+            .into_iter()
+            .map(|(i, (len, pat))| (i, len, pat))
+            .collect()
+    }
+
+    /// Checks whether any pattern occurs in `haystack`, stopping at the first occurrence.
+    ///
+    /// Some occurrence is always accepted if there is any, so this is the same as
+    /// checking whether [`Engine::find`] is empty.
+    pub(crate) fn is_match(&self, haystack: &[u8], utf8: bool) -> bool {
+        self.scan(haystack, utf8, |it| it.next().is_some())
+    }
+
+    /// Finds every occurrence of every pattern as `(position, length, pattern)`,
+    /// leaving out those which are not whole words when required or lack the
+    /// context of their rule.
+    pub(crate) fn candidates(&self, haystack: &[u8], utf8: bool) -> Vec<(usize, usize, usize)> {
+        self.scan(haystack, utf8, |it| it.collect())
+    }
+
+    /// Calls `f` with an iterator over every occurrence of every pattern.
+    fn scan<T>(
+        &self,
+        haystack: &[u8],
+        utf8: bool,
+        f: impl FnOnce(&mut dyn Iterator<Item = (usize, usize, usize)>) -> T,
+    ) -> T {
+        let words = Words::new(haystack, self.options.word);
+        let keep = |start, len, pat: usize| {
+            (!(utf8 && len == 0) || is_char_boundary(haystack, start))
+                && words.is_whole(start, start + len)
+                && self.contexts.get(pat).is_none_or(|contexts| {
+                    contexts
+                        .iter()
+                        .all(|ctx| self.has_context(haystack, start, start + len, ctx))
+                })
+        };
+
+        if self.options.case == CaseSensitivity::Unicode {
+            let folded = Folded::new(haystack);
+            f(&mut self
+                .searcher
+                .find_overlapping_iter(&folded.text)
+                .filter_map(|m| {
+                    // occurrences of folded patterns always start and end on char boundaries,
+                    // except for empty ones
+                    let start = folded.origin(m.start())?;
+                    let end = folded.origin(m.end())?;
+                    Some((start, end - start, self.literals[m.pattern().as_usize()]))
+                })
+                .chain(self.regex_candidates(haystack, utf8))
+                .filter(|&(start, len, pat)| keep(start, len, pat)))
+        } else {
+            f(&mut self
+                .searcher
+                .find_overlapping_iter(haystack)
+                .map(|m| (m.start(), m.len(), self.literals[m.pattern().as_usize()]))
+                .chain(self.regex_candidates(haystack, utf8))
+                .filter(|&(start, len, pat)| keep(start, len, pat)))
+        }
+    }
+
+    /// Checks whether the context constraint `ctx` holds for the occurrence at
+    /// `start..end`.
+    fn has_context(&self, haystack: &[u8], start: usize, end: usize, ctx: &Context) -> bool {
+        let text = ctx.text.as_bytes();
+        let found = match self.options.case {
+            CaseSensitivity::Sensitive if ctx.after => haystack[end..].starts_with(text),
+            CaseSensitivity::Sensitive => haystack[..start].ends_with(text),
+            CaseSensitivity::Ascii if ctx.after => haystack
+                .get(end..end + text.len())
+                .is_some_and(|next| next.eq_ignore_ascii_case(text)),
+            CaseSensitivity::Ascii => start
+                .checked_sub(text.len())
+                .is_some_and(|i| haystack[i..start].eq_ignore_ascii_case(text)),
+            CaseSensitivity::Unicode => {
+                // the chars of the context are at most 4 bytes each in the haystack
+                let n = ctx.text.chars().count() * 4;
+                let same =
+                    |c: char, h: Option<char>| h.is_some_and(|h| fold_char(c) == fold_char(h));
+                if ctx.after {
+                    let next = String::from_utf8_lossy(&haystack[end..haystack.len().min(end + n)]);
+                    let mut next = next.chars();
+                    ctx.text.chars().all(|c| same(c, next.next()))
+                } else {
+                    let prev = String::from_utf8_lossy(&haystack[start.saturating_sub(n)..start]);
+                    let mut prev = prev.chars().rev();
+                    ctx.text.chars().rev().all(|c| same(c, prev.next()))
+                }
+            }
+        };
+        found != ctx.negated
+    }
+
+    /// Finds the matches of each regex rule as `(position, length, rule)`.
+    ///
+    /// Unlike literal patterns, a regex only reports the non-overlapping matches
+    /// found by `Regex::find_iter`.
+    #[cfg(feature = "regex")]
+    fn regex_candidates<'s>(
+        &'s self,
+        haystack: &'s [u8],
+        utf8: bool,
+    ) -> impl Iterator<Item = (usize, usize, usize)> + 's {
+        self.regexes
+            .iter()
+            .flat_map(move |(rule, re)| {
+                re.find_iter(haystack)
+                    .map(move |m| (m.start(), m.len(), *rule))
+            })
+            // `(?-u)` regexes may match parts of chars
+            .filter(move |&(start, len, _)| {
+                !utf8
+                    || (is_char_boundary(haystack, start)
+                        && is_char_boundary(haystack, start + len))
+            })
+    }
+
+    /// Returns the rule of the first regex which can match the empty string.
+    #[cfg(feature = "regex")]
+    pub(crate) fn empty_regex(&self) -> Option<usize> {
+        let mut parser = regex_syntax::ParserBuilder::new();
+        parser
+            .case_insensitive(self.options.case == CaseSensitivity::Unicode)
+            .utf8(false);
+        self.regexes
+            .iter()
+            .find(|(_, re)| {
+                parser
+                    .build()
+                    .parse(re.as_str())
+                    .is_ok_and(|hir| hir.properties().minimum_len() == Some(0))
+            })
+            .map(|&(rule, _)| rule)
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn regex_candidates(&self, _: &[u8], _: bool) -> std::iter::Empty<(usize, usize, usize)> {
+        std::iter::empty()
+    }
+
+    /// Expands the capture groups in `template` for the match of rule `rule` at
+    /// `pos`, or returns `None` if the rule is not a regex.
+    #[cfg(feature = "regex")]
+    pub(crate) fn expand(
+        &self,
+        rule: usize,
+        haystack: &[u8],
+        pos: usize,
+        template: &[u8],
+    ) -> Option<Vec<u8>> {
+        let i = self
+            .regexes
+            .binary_search_by_key(&rule, |&(rule, _)| rule)
+            .ok()?;
+        // the leftmost match from `pos` is the one found at `pos`
+        let caps = self.regexes[i].1.captures_at(haystack, pos)?;
+        let mut expanded = Vec::new();
+        caps.expand(template, &mut expanded);
+        Some(expanded)
+    }
+}
+
+/// Replaces each match with the result of calling `replacement` with the haystack
+/// and the match.
+///
+/// `matches` must be ordered by position and must not overlap.
+pub(crate) fn splice<'a>(
+    haystack: &[u8],
+    matches: &[(usize, usize, usize)],
+    replacement: impl Fn(&[u8], (usize, usize, usize)) -> Cow<'a, [u8]>,
+) -> Vec<u8> {
+    let news: Vec<_> = matches.iter().map(|&m| replacement(haystack, m)).collect();
+    let len = matches
+        .iter()
+        .zip(&news)
+        .fold(haystack.len(), |len, (&(_, l, _), new)| len - l + new.len());
+    let mut result = Vec::with_capacity(len);
+    let mut end = 0usize;
+
+    for (&(pos, len, _), new) in matches.iter().zip(&news) {
+        result.extend_from_slice(&haystack[end..pos]);
+        result.extend_from_slice(new);
+        end = pos + len;
+    }
+    result.extend_from_slice(&haystack[end..]);
+
+    result
+}
+
+/// Same as `str::is_char_boundary`, but for possibly invalid UTF-8.
+fn is_char_boundary(haystack: &[u8], i: usize) -> bool {
+    // 0b10xx_xxxx is a continuation byte
+    haystack.get(i).is_none_or(|&b| (b as i8) >= -0x40)
+}
+
+/// Orders `candidates` by pattern and then by position, dropping occurrences
+/// which overlap a previous occurrence of the same pattern.
+///
+/// Like `str::match_indices`, the leftmost occurrence wins.
+fn self_greedy(mut candidates: Vec<(usize, usize, usize)>) -> Vec<(usize, usize, usize)> {
+    candidates.sort_unstable_by_key(|&(i, _, pat)| (pat, i));
+
+    let mut last = None;
+    let mut last_end = 0;
+    candidates.retain(|&(i, len, pat)| {
+        if last != Some(pat) {
+            last = Some(pat);
+            last_end = 0;
+        }
+        if i < last_end {
+            return false;
+        }
+        last_end = i + len;
+        true
+    });
+    candidates
+}
+
+/// Checks whether `[i, i + len)` overlaps any of the accepted matches in `indices`.
+///
+/// Both neighbours have to be checked: the nearest match starting at or before `i`
+/// must end before `i`, and the nearest match starting after `i` must start at or
+/// after `i + len`. Two matches starting at the same position always overlap.
+fn overlaps<T>(indices: &BTreeMap<usize, (usize, T)>, i: usize, len: usize) -> bool {
+    let before = indices
+        .range(..=i)
+        .next_back()
+        .is_some_and(|(&pos, &(l, _))| pos == i || pos + l > i);
+    let after = indices
+        .range(i + 1..)
+        .next()
+        .is_some_and(|(&pos, _)| pos < i + len);
+    before || after
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// An error from validating patterns or edits, or from building a replacer.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MultirepError {
+    /// The pattern at `index` is empty, or is a regex which can match the empty
+    /// string.
+    EmptyPattern { index: usize },
+    /// The pattern at `index` is the same as the one at `first`.
+    DuplicatePattern { first: usize, index: usize },
+    /// The pattern at `index` is the same as the one at `first`, but has a
+    /// different replacement.
+    ConflictingReplacements { first: usize, index: usize },
+    /// Cascading replacement came back to an earlier text. `rules` are the
+    /// patterns replaced since then, in order of index.
+    Cycle { rules: Vec<usize> },
+    /// Cascading replacement still changed the text after `max_passes` passes.
+    TooManyPasses { max_passes: usize },
+    /// The range of the edit at `index` is reversed, out of bounds or not on char
+    /// boundaries.
+    InvalidEdit { index: usize, range: Range<usize> },
+    /// The edits at `first` and `second` overlap.
+    OverlappingEdits { first: usize, second: usize },
+    /// The target at `index` of a permutation is not one of its sources, or is
+    /// the target of an earlier source too.
+    NotAPermutation { index: usize },
+    /// The regex of the rule at `index` failed to compile.
+    #[cfg(feature = "regex")]
+    Regex { index: usize, source: regex::Error },
+}
+
+impl fmt::Display for MultirepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultirepError::EmptyPattern { index } => {
+                write!(f, "pattern {index} can match the empty string")
+            }
+            MultirepError::DuplicatePattern { first, index } => {
+                write!(f, "pattern {index} is the same as pattern {first}")
+            }
+            MultirepError::ConflictingReplacements { first, index } => write!(
+                f,
+                "pattern {index} is the same as pattern {first}, but has another replacement"
+            ),
+            MultirepError::Cycle { rules } => {
+                write!(f, "patterns {rules:?} replace each other in a cycle")
+            }
+            MultirepError::TooManyPasses { max_passes } => {
+                write!(f, "text still changes after {max_passes} passes")
+            }
+            MultirepError::InvalidEdit { index, range } => {
+                write!(f, "edit {index} has invalid range {range:?}")
+            }
+            MultirepError::OverlappingEdits { first, second } => {
+                write!(f, "edits {first} and {second} overlap")
+            }
+            MultirepError::NotAPermutation { index } => {
+                write!(f, "target {index} is not a source or is used twice")
+            }
+            #[cfg(feature = "regex")]
+            MultirepError::Regex { index, source } => {
+                write!(f, "invalid regex in rule {index}: {source}")
+            }
+        }
+    }
+}
+
+impl Error for MultirepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "regex")]
+            MultirepError::Regex { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,290 @@
+/// Folds the case of `c`, so that two chars fold to the same char exactly when
+/// they are equal under Unicode simple case folding.
+///
+/// The folded char is not always the one from `CaseFolding.txt` (Cherokee folds
+/// to lowercase here), but the equivalence classes are the same.
+pub(crate) fn fold_char(c: char) -> char {
+    // the only char whose uppercase folds to something else than the char itself
+    if c == 'ı' {
+        return c;
+    }
+    match single(c.to_lowercase()) {
+        // lowercasing the uppercase also catches chars like `ς` and `ſ`,
+        // which fold to another lowercase char
+        Some(lower) => single(lower.to_uppercase())
+            .and_then(|upper| single(upper.to_lowercase()))
+            .unwrap_or(lower),
+        // chars like `İ` only have a full case folding
+        None => c,
+    }
+}
+
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Case-folded text which remembers where each char came from.
+///
+/// Every char is folded to exactly one char, so char boundaries of the folded
+/// text correspond to char boundaries of the original one. Bytes which are not
+/// valid UTF-8 are kept as they are.
+pub(crate) struct Folded {
+    pub(crate) text: Vec<u8>,
+    origins: Vec<Option<usize>>,
+}
+
+impl Folded {
+    pub(crate) fn new(s: &[u8]) -> Self {
+        let mut text = Vec::with_capacity(s.len());
+        let mut origins = Vec::with_capacity(s.len() + 1);
+        let mut pos = 0;
+
+        for chunk in s.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                let folded = fold_char(c);
+                origins.push(Some(pos));
+                origins.extend((1..folded.len_utf8()).map(|_| None));
+                text.extend_from_slice(folded.encode_utf8(&mut [0; 4]).as_bytes());
+                pos += c.len_utf8();
+            }
+            for &b in chunk.invalid() {
+                origins.push(Some(pos));
+                text.push(b);
+                pos += 1;
+            }
+        }
+        origins.push(Some(pos));
+
+        Self { text, origins }
+    }
+
+    /// Returns the number of chars (or invalid bytes) in the text.
+    pub(crate) fn chars(&self) -> usize {
+        self.origins.iter().filter(|o| o.is_some()).count() - 1
+    }
+
+    /// Returns the position in the original text corresponding to position `i`
+    /// of the folded text, if `i` is on a char boundary.
+    pub(crate) fn origin(&self, i: usize) -> Option<usize> {
+        self.origins[i]
+    }
+}
+
+/// Rewrites the regex `pattern` so that ASCII letters match regardless of their
+/// case, leaving every other char as it is, or returns `None` if it is invalid.
+///
+/// The `i` flag of the `regex` crate uses Unicode simple case folding, so inline
+/// `i` flags are applied here and removed, and the result must be built without
+/// case insensitivity.
+#[cfg(feature = "regex")]
+pub(crate) fn fold_ascii_regex(pattern: &str) -> Option<String> {
+    let mut ast = regex_syntax::ast::parse::Parser::new()
+        .parse(pattern)
+        .ok()?;
+    ascii_regex::fold(&mut ast, &mut true);
+    let mut folded = String::new();
+    regex_syntax::ast::print::Printer::new()
+        .print(&ast, &mut folded)
+        .ok()?;
+    Some(folded)
+}
+
+#[cfg(feature = "regex")]
+mod ascii_regex {
+    use std::mem;
+
+    use regex_syntax::ast::{
+        Ast, ClassAsciiKind, ClassBracketed, ClassSet, ClassSetItem, ClassSetRange, ClassSetUnion,
+        Flag, Flags, FlagsItemKind, GroupKind, Literal, LiteralKind, Span,
+    };
+
+    /// Folds the letters of `ast`, where `fold_case` tells whether the `i` flag
+    /// is set, as `(?i)` and `(?-i)` change it.
+    pub(super) fn fold(ast: &mut Ast, fold_case: &mut bool) {
+        match ast {
+            Ast::Flags(set) => {
+                if let Some(state) = take_case_flag(&mut set.flags) {
+                    *fold_case = state;
+                }
+                if set.flags.items.is_empty() {
+                    *ast = Ast::empty(set.span);
+                }
+            }
+            Ast::Literal(lit) if *fold_case => {
+                if let Some(other) = other_case(lit.c) {
+                    let union = ClassSetUnion {
+                        span: lit.span,
+                        items: vec![
+                            ClassSetItem::Literal((**lit).clone()),
+                            ClassSetItem::Literal(verbatim(lit.span, other)),
+                        ],
+                    };
+                    *ast = Ast::class_bracketed(ClassBracketed {
+                        span: lit.span,
+                        negated: false,
+                        kind: ClassSet::union(union),
+                    });
+                }
+            }
+            // classes are folded before being negated, as `[^a]` matches neither
+            // `a` nor `A`
+            Ast::ClassBracketed(class) if *fold_case => fold_set(&mut class.kind),
+            Ast::Repetition(rep) => fold(&mut rep.ast, fold_case),
+            Ast::Group(group) => {
+                // flags set in a group only last until its end
+                let mut inner = *fold_case;
+                if let GroupKind::NonCapturing(flags) = &mut group.kind {
+                    if let Some(state) = take_case_flag(flags) {
+                        inner = state;
+                    }
+                }
+                fold(&mut group.ast, &mut inner);
+            }
+            Ast::Alternation(alt) => alt.asts.iter_mut().for_each(|ast| fold(ast, fold_case)),
+            Ast::Concat(concat) => concat.asts.iter_mut().for_each(|ast| fold(ast, fold_case)),
+            // Perl classes are the same in both cases, and Unicode ones are
+            // used as they are written
+            _ => {}
+        }
+    }
+
+    /// Removes the `i` flag from `flags`, returning whether it was set or unset.
+    fn take_case_flag(flags: &mut Flags) -> Option<bool> {
+        let state = flags.flag_state(Flag::CaseInsensitive)?;
+        flags
+            .items
+            .retain(|item| item.kind != FlagsItemKind::Flag(Flag::CaseInsensitive));
+        // `(?-)` is not a valid flag group
+        if flags
+            .items
+            .last()
+            .is_some_and(|item| item.kind.is_negation())
+        {
+            flags.items.pop();
+        }
+        Some(state)
+    }
+
+    fn fold_set(set: &mut ClassSet) {
+        match set {
+            ClassSet::Item(item) => fold_item(item),
+            ClassSet::BinaryOp(op) => {
+                fold_set(&mut op.lhs);
+                fold_set(&mut op.rhs);
+            }
+        }
+    }
+
+    /// Adds the other case of the ASCII letters matched by `item`.
+    fn fold_item(item: &mut ClassSetItem) {
+        let others: Vec<_> = match item {
+            ClassSetItem::Literal(lit) => other_case(lit.c)
+                .map(|c| ClassSetItem::Literal(verbatim(lit.span, c)))
+                .into_iter()
+                .collect(),
+            ClassSetItem::Range(range) => [('A', 'Z'), ('a', 'z')]
+                .into_iter()
+                .filter_map(|(first, last)| {
+                    // the letters of the range in one case
+                    let start = range.start.c.max(first);
+                    let end = range.end.c.min(last);
+                    if start > end {
+                        return None;
+                    }
+                    Some(ClassSetItem::Range(ClassSetRange {
+                        span: range.span,
+                        start: verbatim(range.span, other_case(start)?),
+                        end: verbatim(range.span, other_case(end)?),
+                    }))
+                })
+                .collect(),
+            ClassSetItem::Ascii(class) => {
+                if matches!(class.kind, ClassAsciiKind::Upper | ClassAsciiKind::Lower) {
+                    class.kind = ClassAsciiKind::Alpha;
+                }
+                return;
+            }
+            ClassSetItem::Bracketed(class) => return fold_set(&mut class.kind),
+            ClassSetItem::Union(union) => return union.items.iter_mut().for_each(fold_item),
+            _ => return,
+        };
+
+        if !others.is_empty() {
+            let span = *item.span();
+            let mut items = vec![mem::replace(item, ClassSetItem::Empty(span))];
+            items.extend(others);
+            *item = ClassSetItem::Union(ClassSetUnion { span, items });
+        }
+    }
+
+    /// Returns `c` in the other case if it is an ASCII letter.
+    fn other_case(c: char) -> Option<char> {
+        c.is_ascii_alphabetic().then_some((c as u8 ^ 0x20) as char)
+    }
+
+    fn verbatim(span: Span, c: char) -> Literal {
+        Literal {
+            span,
+            kind: LiteralKind::Verbatim,
+            c,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn simple_folding() {
+        let same = |a, b| fold_char(a) == fold_char(b);
+
+        assert!(same('A', 'a'));
+        assert!(same('K', 'k'));
+        assert!(same('\u{212A}', 'k'));
+        assert!(same('ſ', 'S'));
+        assert!(same('ς', 'Σ'));
+        assert!(same('ẞ', 'ß'));
+        assert!(same('Ꭰ', 'ꭰ'));
+        assert!(!same('ı', 'i'));
+        assert!(!same('İ', 'i'));
+        assert!(!same('ß', 's'));
+    }
+
+    #[test]
+    fn origins() {
+        // the Kelvin sign is 3 bytes long, but folds to a 1 byte `k`
+        let f = Folded::new("a\u{212A}é\u{ff}".as_bytes());
+
+        assert_eq!("akéÿ".as_bytes(), &f.text[..]);
+        assert_eq!(Some(1), f.origin(1));
+        assert_eq!(Some(4), f.origin(2));
+        assert_eq!(None, f.origin(3));
+        assert_eq!(Some(6), f.origin(4));
+        assert_eq!(Some(8), f.origin(6));
+
+        let f = Folded::new(b"A\xffB");
+        assert_eq!(b"a\xffb", &f.text[..]);
+        assert_eq!(Some(2), f.origin(2));
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn ascii_regex() {
+        let fold = |pat| fold_ascii_regex(pat).unwrap();
+
+        assert_eq!("[hH].[lL][lL][oO]", fold("h.llo"));
+        assert_eq!("[éè][tT]", fold("[éè]t"));
+        assert_eq!(
+            r"[^xX][b-dB-D\d][[:alpha:]]",
+            fold(r"[^x][b-d\d][[:upper:]]")
+        );
+        assert_eq!("[_-~A-Z]", fold("[_-~]"));
+        assert_eq!("[aA](?:b)[cC](?-s)d(?x:e)", fold("a(?-i:b)c(?-is)d(?x:e)"));
+        assert_eq!("a[bB]|[cC]", fold("(?-i)a(?i)b|c"));
+        assert_eq!(None, fold_ascii_regex("("));
+    }
+}
@@ -1,4 +1,30 @@
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::ops::Range;
+
+mod case;
+mod diff;
+mod edit;
+mod engine;
+mod error;
+mod fold;
+mod matches;
+mod ranges;
+mod replacer;
+mod report;
+mod rule;
+mod stream;
+mod word;
+
+pub use diff::{diff, Diff, DiffLine, Hunk};
+pub use edit::apply_edits;
+pub use engine::{CaseSensitivity, MatchKind};
+pub use error::MultirepError;
+pub use matches::{FindIter, Match};
+pub use replacer::{BytesMultiReplacer, MultiReplacer, MultiReplacerBuilder};
+pub use report::{PatternCount, ReplaceReport, ReplacedSpan};
+pub use rule::{Pattern, Rule, RuleSet};
+pub use word::WordBoundary;
 
 /// Multiple version of `str::replace` which replaces multiple patterns at a time.
 ///
@@ -25,42 +51,109 @@ use std::collections::BTreeMap;
 /// assert_eq!("Minami is kawaii", multi_replace("Hana is cute", &[("Hana", "Minami"), ("cute", "kawaii"), ("kawaii", "hot")]));
 /// ```
 ///
+/// A match overlapping a match of an earlier pattern is discarded, no matter which one
+/// starts first, so replaced spans never overlap.
+///
+/// ```
+/// use multirep::multi_replace;
+/// assert_eq!("aX", multi_replace("abc", &[("bc", "X"), ("ab", "Y")]));
+/// ```
+///
+/// Patterns are not validated: an empty pattern inserts its replacement at every
+/// char boundary not covered by another match, and a repeated pattern never
+/// matches. [`MultiReplacer::try_new`] rejects both.
+///
+/// ```
+/// use multirep::multi_replace;
+/// assert_eq!("-a-b-", multi_replace("ab", &[("", "-")]));
+/// ```
+///
+/// To apply the same patterns to many strings, build a [`MultiReplacer`] once instead.
 pub fn multi_replace(s: &str, pats: &[(&str, &str)]) -> String {
-    let mut indices = BTreeMap::new();
-
-    for (pat, new) in pats {
-        for (i, p) in s.match_indices(pat) {
-            if indices
-                .range(..=i)
-                .next_back()
-                .map(|(pos, (len, _))| pos + len <= i)
-                .unwrap_or(true)
-            {
-                indices.insert(i, (p.len(), *new));
-            }
-        }
-    }
+    MultiReplacer::new(pats).replace(s)
+}
 
-    let mut result = String::new();
-    let mut end = 0usize;
+/// Same as [`multi_replace`], but borrows `s` if no pattern occurs in it.
+///
+/// ```
+/// use std::borrow::Cow;
+///
+/// use multirep::multi_replace_cow;
+///
+/// let s = "Hana is cute";
+/// assert!(matches!(multi_replace_cow(s, &[("Rica", "Minami")]), Cow::Borrowed(_)));
+/// assert_eq!("Minami is cute", multi_replace_cow(s, &[("Hana", "Minami")]));
+/// ```
+pub fn multi_replace_cow<'a>(s: &'a str, pats: &[(&str, &str)]) -> Cow<'a, str> {
+    MultiReplacer::new(pats).replace_cow(s)
+}
 
-    for (pos, (len, new)) in indices {
-        // SAFETY: pos is returned by `str::match_indices`, which is valid
-        // end >= 0 since it starts at 0 and only increases
-        // end < pos since `str::match_indices` doesn't overlap
-        // len is the length of one pattern string, so `pos + len`(`end`) should be on unicode boundaries.
-        result.push_str(unsafe { s.get_unchecked(end..pos) });
-        result.push_str(new);
-        end = pos + len;
-    }
+/// Same as [`multi_replace`], but also reports which patterns were replaced where.
+///
+/// See [`MultiReplacer::replace_report`].
+pub fn multi_replace_report(s: &str, pats: &[(&str, &str)]) -> (String, ReplaceReport) {
+    MultiReplacer::new(pats).replace_report(s)
+}
 
-    if end < s.len() {
-        // SAFETY: end >= 0 and is on unicode boundaries as above
-        // end < s.len()
-        result.push_str(unsafe { s.get_unchecked(end..) });
-    }
+/// Same as [`multi_replace`], but only replaces matches within the byte `ranges`.
+///
+/// See [`MultiReplacer::replace_in_ranges`].
+///
+/// ```
+/// use multirep::multi_replace_in_ranges;
+/// assert_eq!("Hana is kawaii", multi_replace_in_ranges("Hana is cute", &[("Hana", "Minami"), ("cute", "kawaii")], &[5..12]));
+/// ```
+pub fn multi_replace_in_ranges(s: &str, pats: &[(&str, &str)], ranges: &[Range<usize>]) -> String {
+    MultiReplacer::new(pats).replace_in_ranges(s, ranges)
+}
+
+/// Same as [`multi_replace`], but leaves the byte `ranges` untouched.
+///
+/// See [`MultiReplacer::replace_excluding`].
+///
+/// ```
+/// use multirep::multi_replace_excluding;
+/// assert_eq!("Minami is cute", multi_replace_excluding("Hana is cute", &[("Hana", "Minami"), ("cute", "kawaii")], &[5..12]));
+/// ```
+pub fn multi_replace_excluding(s: &str, pats: &[(&str, &str)], ranges: &[Range<usize>]) -> String {
+    MultiReplacer::new(pats).replace_excluding(s, ranges)
+}
+
+/// Same as [`multi_replace`], but computes each replacement by calling `f` on the [`Match`].
+///
+/// `f` is called once for each accepted match, in order of position.
+///
+/// ```
+/// use std::borrow::Cow;
+///
+/// use multirep::multi_replace_with;
+///
+/// let mut n = 0;
+/// let r = multi_replace_with("Hana and Minami and Hana", &["Hana", "Minami"], |m| {
+///     n += 1;
+///     Cow::Owned(format!("{}#{}({})", m.as_str(), n, m.pattern()))
+/// });
+/// assert_eq!("Hana#1(0) and Minami#2(1) and Hana#3(0)", r);
+/// ```
+pub fn multi_replace_with<'h, 'a, F>(s: &'h str, pats: &[&str], f: F) -> String
+where
+    F: FnMut(&Match<'h>) -> Cow<'a, str>,
+{
+    let pats: Vec<_> = pats.iter().map(|&pat| (pat, "")).collect();
+    MultiReplacer::new(&pats).replace_with(s, f)
+}
 
-    result
+/// Finds the matches [`multi_replace`] would replace, without replacing them.
+///
+/// ```
+/// use multirep::find_iter;
+///
+/// let found: Vec<_> = find_iter("abc", &["bc", "ab"]).map(|m| (m.pattern(), m.as_str())).collect();
+/// assert_eq!(vec![(0, "bc")], found);
+/// ```
+pub fn find_iter<'h>(s: &'h str, pats: &[&str]) -> FindIter<'h> {
+    let pats: Vec<_> = pats.iter().map(|&pat| (pat, "")).collect();
+    MultiReplacer::new(&pats).find_iter(s)
 }
 
 /// Exchanges two patterns in a string
@@ -69,14 +162,72 @@ pub fn multi_replace(s: &str, pats: &[(&str, &str)]) -> String {
 /// assert_eq!("foo bar", exchange("bar foo", "foo", "bar"));
 /// ```
 pub fn exchange(s: &str, a: &str, b: &str) -> String {
-    // if a contains b, searching b first will also match a substring of a.
-    // search the longer one to avoid such a situation.
-    let pat = if a.len() > b.len() {
-        [(a, b), (b, a)]
-    } else {
-        [(b, a), (a, b)]
-    };
-    multi_replace(s, &pat)
+    MultiReplacer::exchange(a, b).replace(s)
+}
+
+/// Rotates any number of patterns in a string at once, like [`exchange`] does
+/// for two.
+///
+/// `mapping` must be a permutation: every source appears once, and the targets
+/// are the sources in some order. As in [`exchange`], a longer pattern wins over
+/// a shorter one it overlaps, and an earlier one over a later one of the same
+/// length.
+///
+/// ```
+/// use multirep::permute;
+///
+/// let s = "Hana, Minami and Sora";
+/// let r = permute(s, &[("Hana", "Minami"), ("Minami", "Sora"), ("Sora", "Hana")]);
+/// assert_eq!("Minami, Sora and Hana", r.unwrap());
+/// assert!(permute(s, &[("Hana", "Minami"), ("Minami", "Sora")]).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns [`MultirepError::EmptyPattern`] if a source is empty,
+/// [`MultirepError::DuplicatePattern`] if a source appears twice, and
+/// [`MultirepError::NotAPermutation`] if a target is not a source or appears twice.
+pub fn permute(s: &str, mapping: &[(&str, &str)]) -> Result<String, MultirepError> {
+    for (i, &(from, _)) in mapping.iter().enumerate() {
+        if from.is_empty() {
+            return Err(MultirepError::EmptyPattern { index: i });
+        }
+        if let Some(first) = mapping[..i].iter().position(|&(f, _)| f == from) {
+            return Err(MultirepError::DuplicatePattern { first, index: i });
+        }
+    }
+    for (i, &(_, to)) in mapping.iter().enumerate() {
+        let is_source = mapping.iter().any(|&(from, _)| from == to);
+        if !is_source || mapping[..i].iter().any(|&(_, t)| t == to) {
+            return Err(MultirepError::NotAPermutation { index: i });
+        }
+    }
+
+    let mut mapping = mapping.to_vec();
+    mapping.sort_by_key(|&(from, _)| Reverse(from.len()));
+    Ok(multi_replace(s, &mapping))
+}
+
+/// Same as [`multi_replace`], but for data which is not necessarily valid UTF-8.
+///
+/// ```
+/// use multirep::multi_replace_bytes;
+///
+/// let r = multi_replace_bytes(b"Hana\xffis cute", &[(b"\xff", b" "), (b"cute", b"kawaii")]);
+/// assert_eq!(r, b"Hana is kawaii");
+/// ```
+pub fn multi_replace_bytes(s: &[u8], pats: &[(&[u8], &[u8])]) -> Vec<u8> {
+    BytesMultiReplacer::new(pats).replace(s)
+}
+
+/// Same as [`exchange`], but for data which is not necessarily valid UTF-8.
+///
+/// ```
+/// use multirep::exchange_bytes;
+/// assert_eq!(b"foo\xffbar", &exchange_bytes(b"bar\xfffoo", b"foo", b"bar")[..]);
+/// ```
+pub fn exchange_bytes(s: &[u8], a: &[u8], b: &[u8]) -> Vec<u8> {
+    BytesMultiReplacer::exchange(a, b).replace(s)
 }
 
 #[cfg(test)]
@@ -118,6 +269,191 @@ mod test {
         )
     }
 
+    #[test]
+    fn overlap_later_match() {
+        assert_eq!("aX", multi_replace("abc", &[("bc", "X"), ("ab", "Y")]));
+        assert_eq!(
+            "Hana and Minami",
+            multi_replace("Hana and Aoi", &[("Aoi", "Minami"), ("d A", "-")])
+        );
+        assert_eq!(
+            "xYz",
+            multi_replace("xabcz", &[("abc", "Y"), ("xa", "1"), ("cz", "2")])
+        );
+    }
+
+    #[test]
+    fn match_kind() {
+        let replace = |kind, s| {
+            MultiReplacer::builder()
+                .match_kind(kind)
+                .build(&[("ab", "1"), ("bcd", "2"), ("b", "3")])
+                .replace(s)
+        };
+
+        assert_eq!("1cd", replace(MatchKind::Priority, "abcd"));
+        assert_eq!("a2", replace(MatchKind::Rightmost, "abcd"));
+        assert_eq!("1cd", replace(MatchKind::LeftmostFirst, "abcd"));
+        assert_eq!("x2", replace(MatchKind::LeftmostFirst, "xbcd"));
+        assert_eq!("x2", replace(MatchKind::LeftmostLongest, "xbcd"));
+        assert_eq!("x3c", replace(MatchKind::Priority, "xbc"));
+    }
+
+    #[test]
+    fn bytes() {
+        let s = b"Hana\xfe is \xffcute";
+        let pats: &[(&[u8], &[u8])] = &[(b"\xffcute", b"kawaii"), (b"\xfe", b""), (b"\xff", b"!")];
+
+        assert_eq!(&b"Hana is kawaii"[..], multi_replace_bytes(s, pats));
+        assert_eq!(
+            &b"\xfe\xff"[..],
+            super::exchange_bytes(b"\xff\xfe", b"\xfe", b"\xff")
+        );
+        assert_eq!(
+            multi_replace("abc", &[("bc", "X"), ("ab", "Y")]).as_bytes(),
+            multi_replace_bytes(b"abc", &[(b"bc", b"X"), (b"ab", b"Y")])
+        );
+    }
+
+    #[test]
+    fn replace_with() {
+        let r = multi_replace_with("abc", &["bc", "ab"], |m| {
+            assert_eq!(1..3, m.range());
+            Cow::Borrowed(if m.pattern() == 0 { "X" } else { "Y" })
+        });
+        assert_eq!("aX", r);
+
+        let r = multi_replace_with("Hana is cute", &["Hana", "cute"], |m| {
+            Cow::Owned(m.as_str().chars().rev().collect())
+        });
+        assert_eq!("anaH is etuc", r);
+    }
+
+    #[test]
+    fn cow() {
+        let s = "Hana is cute";
+
+        assert!(matches!(
+            multi_replace_cow(s, &[("Minami", "Hana")]),
+            Cow::Borrowed(b) if std::ptr::eq(b, s)
+        ));
+        let r = multi_replace_cow(s, &[("Hana", "Minami"), ("cute", "kawaii")]);
+        assert_eq!("Minami is kawaii", r);
+        assert_eq!(r.len(), r.into_owned().capacity());
+    }
+
+    #[test]
+    fn report() {
+        let (r, report) = multi_replace_report(
+            "Bouh Aoi and Hana are kawaii",
+            &[
+                ("Bouh", "Both"),
+                ("Aoi", "Minami"),
+                ("oi", "io"),
+                ("Rica", ""),
+            ],
+        );
+
+        assert_eq!("Both Minami and Hana are kawaii", r);
+        assert_eq!(2, report.replaced());
+        assert_eq!(
+            ReplacedSpan {
+                pattern: 1,
+                input: 5..8,
+                output: 5..11
+            },
+            report.spans()[1]
+        );
+        assert_eq!(&r[5..11], "Minami");
+        assert_eq!(
+            [(1, 0), (1, 0), (0, 1), (0, 0)],
+            *report
+                .counts()
+                .iter()
+                .map(|c| (c.replaced, c.shadowed))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn find() {
+        let s = "Bouh Aoi and Hana are kawaii";
+        let pats = [("Bouh", "Both"), ("Aoi", "Minami"), ("oi", "io")];
+        let rep = MultiReplacer::new(&pats);
+
+        let found: Vec<_> = rep.find_iter(s).map(|m| (m.pattern(), m.range())).collect();
+        assert_eq!(vec![(0, 0..4), (1, 5..8)], found);
+        assert_eq!(2, rep.count(s));
+        assert!(rep.is_match(s));
+        assert!(!rep.is_match("Hana"));
+        assert_eq!(0, rep.count("Hana"));
+        assert_eq!(
+            found,
+            super::find_iter(s, &["Bouh", "Aoi", "oi"])
+                .map(|m| (m.pattern(), m.range()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let replace = |case, s| {
+            MultiReplacer::builder()
+                .case_sensitivity(case)
+                .build(&[("hana", "Minami"), ("kawaii", "cute"), ("é", "e")])
+                .replace(s)
+        };
+
+        assert_eq!(
+            "Minami and Minami are cute",
+            replace(CaseSensitivity::Ascii, "HANA and hAna are KAWAII")
+        );
+        assert_eq!("É", replace(CaseSensitivity::Ascii, "É"));
+        assert_eq!("e", replace(CaseSensitivity::Unicode, "É"));
+        // the Kelvin sign is longer than `k`, the replaced span has to cover all of it
+        assert_eq!(
+            "[Minami] is cute!",
+            replace(CaseSensitivity::Unicode, "[HANA] is \u{212A}AWAII!")
+        );
+        let rep = MultiReplacer::builder()
+            .case_sensitivity(CaseSensitivity::Unicode)
+            .build(&[("\u{212A}", "k")]);
+        let found: Vec<_> = rep.find_iter("Kk\u{212A}").map(|m| m.range()).collect();
+        assert_eq!(vec![0..1, 1..2, 2..5], found);
+    }
+
+    #[test]
+    fn preserve_case() {
+        let rep = MultiReplacer::builder()
+            .case_sensitivity(CaseSensitivity::Ascii)
+            .preserve_case(true)
+            .build(&[("hana", "minami"), ("cute", "Kawaii")]);
+
+        assert_eq!(
+            "MINAMI is KAWAII, Minami is Kawaii, minami is kawaii",
+            rep.replace("HANA is CUTE, Hana is Cute, hana is cute")
+        );
+        assert_eq!("MiNami", rep.replace("HaNa"));
+        let (r, report) = rep.replace_report("HANA!");
+        assert_eq!("MINAMI!", r);
+        assert_eq!(0..6, report.spans()[0].output);
+
+        let rep = MultiReplacer::builder()
+            .case_sensitivity(CaseSensitivity::Ascii)
+            .preserve_case(true)
+            .build_bytes(&[(&b"hana"[..], &b"minami"[..]), (b"\xff", b"x")]);
+        assert_eq!(&b"MINAMI\xfex"[..], rep.replace(b"HANA\xfe\xff"));
+    }
+
+    #[test]
+    fn reuse() {
+        let rep = MultiReplacer::new(&[("Hana", "Minami"), ("Minami", "Hana")]);
+
+        assert_eq!("Minami is kawaii", rep.replace("Hana is kawaii"));
+        assert_eq!("Hana is kawaii", rep.replace("Minami is kawaii"));
+        assert_eq!("Rica is kawaii", rep.replace("Rica is kawaii"));
+    }
+
     #[test]
     fn exchange() {
         let s = "Both Hana and Minami are kawaii";
@@ -138,5 +474,347 @@ mod test {
             "Both Hinata and Hina are kawaii",
             super::exchange("Both Hina and Hinata are kawaii", "Hinata", "Hina")
         );
+        // the longer pattern wins over one it partially overlaps
+        assert_eq!("aab", super::exchange("abcd", "ab", "bcd"));
+        assert_eq!("aab", super::exchange("abcd", "bcd", "ab"));
+        assert_eq!(b"aab", &super::exchange_bytes(b"abcd", b"ab", b"bcd")[..]);
+
+        let rep = MultiReplacer::builder()
+            .case_sensitivity(CaseSensitivity::Ascii)
+            .preserve_case(true)
+            .build_exchange("ab", "bcd");
+        assert_eq!("AAB and Bcd", rep.replace("ABCD and Ab"));
+    }
+
+    #[test]
+    fn permute() {
+        let s = "Hina, Hinata and Sora";
+        let rotate = [("Hina", "Hinata"), ("Hinata", "Sora"), ("Sora", "Hina")];
+
+        assert_eq!("Hinata, Sora and Hina", super::permute(s, &rotate).unwrap());
+        assert_eq!(s, super::permute(s, &[]).unwrap());
+        assert_eq!(s, super::permute(s, &[("Hina", "Hina")]).unwrap());
+        assert!(matches!(
+            super::permute(s, &[("Hina", "Sora"), ("Sora", "Hina"), ("Hina", "Sora")]),
+            Err(MultirepError::DuplicatePattern { first: 0, index: 2 })
+        ));
+        assert!(matches!(
+            super::permute(s, &[("Hina", "Sora"), ("Sora", "Sora")]),
+            Err(MultirepError::NotAPermutation { index: 1 })
+        ));
+        assert!(matches!(
+            super::permute(s, &[("Hina", "Hinata")]),
+            Err(MultirepError::NotAPermutation { index: 0 })
+        ));
+        assert!(matches!(
+            super::permute("ab", &[("x", ""), ("", "x")]),
+            Err(MultirepError::EmptyPattern { index: 1 })
+        ));
+
+        // the longer pattern wins over one it partially overlaps
+        let swap = [("ab", "bcd"), ("bcd", "ab")];
+        assert_eq!("aab", super::permute("abcd", &swap).unwrap());
+        let rotate = [("ab", "bcd"), ("bcd", "cd"), ("cd", "ab")];
+        assert_eq!("acd", super::permute("abcd", &rotate).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn regex_rules() {
+        let rep = MultiReplacer::builder()
+            .build_rules(&[
+                Rule::literal("Hana", "Minami"),
+                Rule::regex(r"H\w+", "someone"),
+                Rule::regex(r"(?P<who>\w+) is (\w+)", "${who} is very $2"),
+            ])
+            .unwrap();
+        assert_eq!(3, rep.patterns_len());
+        assert_eq!("Minami and someone", rep.replace("Hana and Hinata"));
+        assert_eq!("Minami is kawaii", rep.replace("Hana is kawaii"));
+        assert_eq!("Sora is very kawaii", rep.replace("Sora is kawaii"));
+
+        let mut out = Vec::new();
+        rep.stream("Sora is kawaii, Hana".as_bytes(), &mut out)
+            .unwrap();
+        assert_eq!(b"Sora is very kawaii, Minami", &out[..]);
+
+        let rep = MultiReplacer::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .case_sensitivity(CaseSensitivity::Ascii)
+            .preserve_case(true)
+            .build_rules(&[
+                Rule::literal("hana", "minami"),
+                Rule::regex("h[a-z]+a", "x$$"),
+            ])
+            .unwrap();
+        assert_eq!("MINAMI X$", rep.replace("HANA HINATA"));
+
+        // regexes fold the same chars as literals
+        let rep = MultiReplacer::builder()
+            .case_sensitivity(CaseSensitivity::Ascii)
+            .build_rules(&[Rule::literal("à", "a"), Rule::regex("é", "e")])
+            .unwrap();
+        assert_eq!("a À e É", rep.replace("à À é É"));
+        let rep = MultiReplacer::builder()
+            .case_sensitivity(CaseSensitivity::Ascii)
+            .build_rules(&[
+                Rule::regex("h.llo", "hi"),
+                Rule::regex(r"[éè]t|\w+", "<$0>"),
+            ])
+            .unwrap();
+        assert_eq!("hi <Été> <cAfé> <ÉT>", rep.replace("HÉLLO Été cAfé ÉT"));
+        let rep = MultiReplacer::builder()
+            .case_sensitivity(CaseSensitivity::Unicode)
+            .build_rules(&[Rule::literal("à", "a"), Rule::regex("é", "e")])
+            .unwrap();
+        assert_eq!("a a e e", rep.replace("à À é É"));
+
+        let err = MultiReplacer::builder()
+            .build_rules(&[Rule::literal("a", "b"), Rule::regex("(", "")])
+            .unwrap_err();
+        assert!(matches!(err, MultirepError::Regex { index: 1, .. }));
+
+        // regexes which can match the empty string are empty patterns
+        for pat in ["x*", r"\b", "(?m)^", "a|"] {
+            let rules = [Rule::literal("a", "b"), Rule::regex(pat, "-")];
+            let err = MultiReplacer::builder().build_rules(&rules).unwrap_err();
+            assert!(
+                matches!(err, MultirepError::EmptyPattern { index: 1 }),
+                "{pat}"
+            );
+            let rep = MultiReplacer::builder()
+                .allow_empty_patterns(true)
+                .build_rules(&rules[1..])
+                .unwrap();
+            assert!(rep.is_match("ab"), "{pat}");
+        }
+        assert!(MultiReplacer::builder()
+            .build_rules(&[Rule::regex("x+|y", "-")])
+            .is_ok());
+    }
+
+    #[test]
+    fn validate() {
+        assert!(MultiReplacer::try_new(&[("Hana", "Minami"), ("hana", "minami")]).is_ok());
+        assert!(matches!(
+            MultiReplacer::try_new(&[("Hana", "Minami"), ("", "-")]),
+            Err(MultirepError::EmptyPattern { index: 1 })
+        ));
+        assert!(matches!(
+            MultiReplacer::try_new(&[("Hana", "Minami"), ("cute", "kawaii"), ("Hana", "Minami")]),
+            Err(MultirepError::DuplicatePattern { first: 0, index: 2 })
+        ));
+
+        let mut builder = MultiReplacer::builder();
+        builder.case_sensitivity(CaseSensitivity::Unicode);
+        assert!(matches!(
+            builder.try_build(&[("ΣΟΦΙΑ", "Sofia"), ("σοφια", "sofia")]),
+            Err(MultirepError::ConflictingReplacements { first: 0, index: 1 })
+        ));
+        assert!(matches!(
+            builder.try_build_bytes(&[(&b"\xffa"[..], &b""[..]), (b"\xffA", b"")]),
+            Err(MultirepError::DuplicatePattern { first: 0, index: 1 })
+        ));
+        assert!(matches!(
+            builder.build_rules(&[Rule::literal("a", "b"), Rule::literal("", "c")]),
+            Err(MultirepError::EmptyPattern { index: 1 })
+        ));
+
+        let rep = builder
+            .allow_empty_patterns(true)
+            .try_build(&[("", "-"), ("a", "b")])
+            .unwrap();
+        assert_eq!("-é-a-", rep.replace("éa"));
+        assert_eq!("-a-", builder.build(&[("", "-"), ("a", "b")]).replace("a"));
+    }
+
+    #[test]
+    fn cascading() {
+        let rep = MultiReplacer::new(&[("a", "b"), ("b", "c"), ("cc", "d")]);
+        assert_eq!(
+            ("dd".to_owned(), 3),
+            rep.replace_cascading("abab", 10).unwrap()
+        );
+        assert_eq!(
+            ("xyz".to_owned(), 0),
+            rep.replace_cascading("xyz", 0).unwrap()
+        );
+        assert!(matches!(
+            rep.replace_cascading("abab", 2),
+            Err(MultirepError::TooManyPasses { max_passes: 2 })
+        ));
+
+        let rep = MultiReplacer::new(&[
+            ("x", "y"),
+            ("Hana", "Minami"),
+            ("Minami", "Sora"),
+            ("Sora", "Hana"),
+        ]);
+        match rep.replace_cascading("x Hana", 10) {
+            Err(MultirepError::Cycle { rules }) => assert_eq!(vec![1, 2, 3], rules),
+            r => panic!("unexpected {r:?}"),
+        }
+
+        let grow = MultiReplacer::new(&[("a", "aa")]);
+        assert!(matches!(
+            grow.replace_cascading("a", 10),
+            Err(MultirepError::TooManyPasses { max_passes: 10 })
+        ));
+    }
+
+    #[test]
+    fn context() {
+        let rep = MultiReplacer::builder()
+            .build_rules(&[
+                Rule::literal("cute", "kawaii").preceded_by("is "),
+                Rule::literal("Hana", "Minami").not_followed_by("ko"),
+                Rule::literal("cute", "pretty")
+                    .followed_by("!")
+                    .not_preceded_by("not "),
+            ])
+            .unwrap();
+        assert_eq!(
+            "Minami is kawaii, Hanako is not cute, pretty! not cute!",
+            rep.replace("Hana is cute, Hanako is not cute, cute! not cute!")
+        );
+        // contexts are looked up in the input, not in replaced text
+        let rep = MultiReplacer::builder()
+            .build_rules(&[
+                Rule::literal("Hana", "is"),
+                Rule::literal(" cute", " kawaii").preceded_by("is"),
+            ])
+            .unwrap();
+        assert_eq!("is cute", rep.replace("Hana cute"));
+
+        let rep = MultiReplacer::builder()
+            .case_sensitivity(CaseSensitivity::Unicode)
+            .build_rules(&[Rule::literal("k", "c")
+                .preceded_by("\u{212A}a")
+                .followed_by("É")])
+            .unwrap();
+        assert_eq!("Kacé, kake", rep.replace("KaKé, kake"));
+        let mut out = Vec::new();
+        rep.stream(&b"KaK\xc3\x89"[..], &mut out).unwrap();
+        assert_eq!("KacÉ".as_bytes(), &out[..]);
+
+        assert!(MultiReplacer::builder()
+            .build_rules(&[
+                Rule::literal("a", "b").preceded_by("x"),
+                Rule::literal("a", "c").preceded_by("y"),
+            ])
+            .is_ok());
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn ranges() {
+        let pats = [("na i", "X"), ("Hana", "Minami"), ("cute", "kawaii")];
+        let s = "Hana is cute";
+
+        // `na i` crosses the edge of the range, so `Hana` is not shadowed
+        assert_eq!("Minami is cute", multi_replace_in_ranges(s, &pats, &[0..5]));
+        assert_eq!("HaXs kawaii", multi_replace_in_ranges(s, &pats, &[0..12]));
+        assert_eq!(
+            "Hana is kawaii",
+            multi_replace_in_ranges(s, &pats, &[8..12, 2..4])
+        );
+        assert_eq!(s, multi_replace_in_ranges(s, &pats, &[]));
+        assert_eq!(
+            "Minami is kawaii",
+            multi_replace_excluding(s, &pats, &[4..5])
+        );
+        assert_eq!(
+            "Hana is kawaii",
+            multi_replace_excluding(s, &pats, &[3..4, 0..2])
+        );
+        assert_eq!("HaXs kawaii", multi_replace_excluding(s, &pats, &[]));
+    }
+
+    #[test]
+    fn edits() {
+        let s = "Hana is cute";
+
+        assert_eq!(s, apply_edits(s, []).unwrap());
+        assert_eq!(
+            "<Hana> is",
+            apply_edits(s, [(4..4, ">"), (7..12, ""), (0..0, "<")]).unwrap()
+        );
+        assert!(matches!(
+            apply_edits(s, [(0..4, "Minami"), (8..12, "kawaii"), (3..5, "")]),
+            Err(MultirepError::OverlappingEdits {
+                first: 0,
+                second: 2
+            })
+        ));
+        assert!(matches!(
+            apply_edits(s, [(4..4, "!"), (4..4, "?")]),
+            Err(MultirepError::OverlappingEdits {
+                first: 0,
+                second: 1
+            })
+        ));
+        assert!(matches!(
+            apply_edits(s, [(0..4, ""), (12..13, "")]),
+            Err(MultirepError::InvalidEdit { index: 1, .. })
+        ));
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 4..2;
+        assert!(apply_edits(s, [(reversed, "")]).is_err());
+        assert!(apply_edits("花", [(0..1, "")]).is_err());
+    }
+
+    #[test]
+    fn rule_set() {
+        let mut rules: RuleSet = vec![Rule::literal("Hana", "Minami")].into();
+        rules.extend([Rule::literal("cute", "kawaii").not_followed_by("!")]);
+        rules.word_boundary = WordBoundary::Ascii;
+        let rep = rules.build().unwrap();
+        assert_eq!(
+            "Minami is kawaii, cute!",
+            rep.replace("Hana is cute, cute!")
+        );
+
+        let rules: RuleSet = [Rule::literal("a", "b"), Rule::literal("a", "c")]
+            .into_iter()
+            .collect();
+        assert!(rules.build().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde() {
+        let mut rules: RuleSet = vec![
+            Rule::literal("Hana", "Minami").not_followed_by("ko"),
+            Rule::literal("cute", "kawaii")
+                .preceded_by("is ")
+                .preceded_by("so "),
+        ]
+        .into();
+        rules.match_kind = MatchKind::LeftmostLongest;
+
+        let json = serde_json::to_string(&rules).unwrap();
+        assert_eq!(rules, serde_json::from_str(&json).unwrap());
+        assert!(json.contains(r#""match-kind":"leftmost-longest""#));
+        assert!(json.contains(r#"{"from":"Hana","to":"Minami","not-followed-by":["ko"]}"#));
+        assert_eq!(
+            rules,
+            toml::from_str(&toml::to_string(&rules).unwrap()).unwrap()
+        );
+
+        let rules: RuleSet =
+            serde_json::from_str(r#"{"rules": [{"from": "a", "to": "b"}]}"#).unwrap();
+        assert_eq!(RuleSet::from(vec![Rule::literal("a", "b")]), rules);
+        assert!(serde_json::from_str::<RuleSet>(r#"{"rules": [{"to": "b"}]}"#).is_err());
+        assert!(
+            serde_json::from_str::<RuleSet>(r#"{"rules": [{"from": "a", "too": "b"}]}"#).is_err()
+        );
+        assert!(serde_json::from_str::<RuleSet>(r#"{"case-sensitivity": "none"}"#).is_err());
+
+        #[cfg(feature = "regex")]
+        {
+            let rules: RuleSet =
+                toml::from_str("[[rules]]\nregex = '(\\w+)!'\nto = '$1?'").unwrap();
+            assert_eq!("Hana?", rules.build().unwrap().replace("Hana!"));
+        }
     }
 }
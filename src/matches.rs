@@ -0,0 +1,97 @@
+use std::iter::FusedIterator;
+use std::ops::Range;
+use std::vec;
+
+/// A match of a pattern in a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Match<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+    pattern: usize,
+}
+
+impl<'h> Match<'h> {
+    pub(crate) fn new(haystack: &'h str, start: usize, end: usize, pattern: usize) -> Self {
+        Self {
+            haystack,
+            start,
+            end,
+            pattern,
+        }
+    }
+
+    /// Returns the index of the matched pattern.
+    pub fn pattern(&self) -> usize {
+        self.pattern
+    }
+
+    /// Returns the byte offset where the match starts.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the byte offset where the match ends.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the byte range of the match.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Returns the matched text.
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.range()]
+    }
+
+    pub(crate) fn haystack(&self) -> &'h str {
+        self.haystack
+    }
+}
+
+/// An iterator over the non-overlapping matches in a string, ordered by position.
+///
+/// Created by [`MultiReplacer::find_iter`](crate::MultiReplacer::find_iter) and
+/// [`find_iter`](crate::find_iter).
+#[derive(Debug, Clone)]
+pub struct FindIter<'h> {
+    haystack: &'h str,
+    matches: vec::IntoIter<(usize, usize, usize)>,
+}
+
+impl<'h> FindIter<'h> {
+    pub(crate) fn new(haystack: &'h str, matches: Vec<(usize, usize, usize)>) -> Self {
+        Self {
+            haystack,
+            matches: matches.into_iter(),
+        }
+    }
+
+    fn to_match(&self, (pos, len, pat): (usize, usize, usize)) -> Match<'h> {
+        Match::new(self.haystack, pos, pos + len, pat)
+    }
+}
+
+impl<'h> Iterator for FindIter<'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.matches.next().map(|m| self.to_match(m))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.matches.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for FindIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.matches.next_back().map(|m| self.to_match(m))
+    }
+}
+
+impl ExactSizeIterator for FindIter<'_> {}
+
+impl FusedIterator for FindIter<'_> {}
@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+/// Sorted, disjoint byte ranges of a haystack.
+#[derive(Debug)]
+pub(crate) struct Ranges(Vec<Range<usize>>);
+
+impl Ranges {
+    /// Sorts `ranges` and merges those which overlap. Ranges which only touch
+    /// are kept apart, so a match can't span both.
+    pub(crate) fn new(ranges: &[Range<usize>]) -> Self {
+        let mut sorted: Vec<_> = ranges.iter().filter(|r| !r.is_empty()).cloned().collect();
+        sorted.sort_unstable_by_key(|r| r.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(sorted.len());
+        for range in sorted {
+            match merged.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        Self(merged)
+    }
+
+    /// Checks whether `start..end` lies within one of the ranges.
+    pub(crate) fn contains(&self, start: usize, end: usize) -> bool {
+        // the last range starting at or before `start`
+        let i = self.0.partition_point(|r| r.start <= start);
+        i > 0 && end <= self.0[i - 1].end
+    }
+
+    /// Checks whether `start..end` shares a byte with one of the ranges. An empty
+    /// `start..end` only does if it is strictly inside a range.
+    pub(crate) fn intersects(&self, start: usize, end: usize) -> bool {
+        // the first range ending after `start`
+        let i = self.0.partition_point(|r| r.end <= start);
+        self.0
+            .get(i)
+            .is_some_and(|r| r.start < end || (start == end && r.start < start))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn contains_and_intersects() {
+        let ranges = Ranges::new(&[6..8, 0..3, 2..4, 4..4, 4..6]);
+
+        assert!(ranges.contains(0, 4));
+        assert!(ranges.contains(4, 4));
+        assert!(!ranges.contains(3, 5));
+        assert!(ranges.contains(8, 8));
+        assert!(!ranges.contains(8, 9));
+        assert!(ranges.intersects(3, 5));
+        assert!(ranges.intersects(2, 2));
+        assert!(!ranges.intersects(0, 0));
+        assert!(!ranges.intersects(4, 4));
+        assert!(!ranges.intersects(8, 10));
+    }
+}
@@ -0,0 +1,869 @@
+use std::borrow::Cow;
+use std::collections::hash_map::{Entry, HashMap};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use crate::case::preserve_case;
+use crate::edit;
+use crate::engine::{splice, CaseSensitivity, Engine, MatchKind, Options};
+use crate::error::MultirepError;
+use crate::fold::Folded;
+use crate::matches::{FindIter, Match};
+use crate::ranges::Ranges;
+use crate::report::{PatternCount, ReplaceReport, ReplacedSpan};
+use crate::rule::{Context, Rule};
+use crate::stream::stream;
+use crate::word::WordBoundary;
+
+/// A builder for [`MultiReplacer`] with non-default options.
+///
+/// ```
+/// use multirep::{MatchKind, MultiReplacerBuilder};
+///
+/// let rep = MultiReplacerBuilder::new()
+///     .match_kind(MatchKind::Rightmost)
+///     .build(&[("aa", "b")]);
+/// assert_eq!("abb", rep.replace("aaaaa"));
+/// ```
+///
+/// # Panics
+///
+/// Building a replacer, here or with [`MultiReplacer::new`] and the like, panics
+/// if the automaton cannot be built, which only happens when the patterns are
+/// too large to fit in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MultiReplacerBuilder {
+    options: Options,
+    allow_empty: bool,
+}
+
+impl MultiReplacerBuilder {
+    /// Creates a builder with default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how overlapping matches are resolved. Defaults to [`MatchKind::Priority`].
+    pub fn match_kind(&mut self, kind: MatchKind) -> &mut Self {
+        self.options.kind = kind;
+        self
+    }
+
+    /// Sets whether patterns match text with different case.
+    /// Defaults to [`CaseSensitivity::Sensitive`].
+    pub fn case_sensitivity(&mut self, case: CaseSensitivity) -> &mut Self {
+        self.options.case = case;
+        self
+    }
+
+    /// Sets whether matches have to be whole words. Defaults to [`WordBoundary::Anywhere`].
+    pub fn word_boundary(&mut self, word: WordBoundary) -> &mut Self {
+        self.options.word = word;
+        self
+    }
+
+    /// Sets whether replacements follow the case of the text they replace.
+    /// Defaults to `false`.
+    ///
+    /// A lowercase, uppercase or titlecase match turns the whole replacement into
+    /// the same case. For any other match, each char of the replacement takes the
+    /// case of the matched char at the same index. This is mostly useful together
+    /// with [`MultiReplacerBuilder::case_sensitivity`].
+    ///
+    /// ```
+    /// use multirep::{CaseSensitivity, MultiReplacer};
+    ///
+    /// // the same as `exchange`, but preserving case
+    /// let rep = MultiReplacer::builder()
+    ///     .case_sensitivity(CaseSensitivity::Unicode)
+    ///     .preserve_case(true)
+    ///     .build_exchange("hana", "minami");
+    /// assert_eq!(
+    ///     "MINAMI, Minami, minami and HANA",
+    ///     rep.replace("HANA, Hana, hana and MINAMI")
+    /// );
+    /// ```
+    ///
+    /// For a [`BytesMultiReplacer`], case is only preserved when both the match and
+    /// the replacement are valid UTF-8.
+    pub fn preserve_case(&mut self, yes: bool) -> &mut Self {
+        self.options.preserve_case = yes;
+        self
+    }
+
+    /// Sets whether [`MultiReplacerBuilder::try_build`] and
+    /// [`MultiReplacerBuilder::build_rules`] accept empty patterns. Defaults to `false`.
+    ///
+    /// An empty pattern matches at every position (every char boundary for `str`
+    /// haystacks), so its replacement is inserted there. As with any other
+    /// pattern, such a match is discarded if it overlaps a match which wins over
+    /// it, and two matches at the same position always overlap. With the default
+    /// [`MatchKind::Priority`], an empty pattern given last therefore only inserts
+    /// between and around the matches of the other patterns.
+    ///
+    /// ```
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::builder()
+    ///     .allow_empty_patterns(true)
+    ///     .try_build(&[("Hana", "Minami"), ("", "|")])
+    ///     .unwrap();
+    /// assert_eq!("Minami| |i|s|", rep.replace("Hana is"));
+    /// assert!(MultiReplacer::try_new(&[("", "|")]).is_err());
+    /// ```
+    ///
+    /// The same goes for regex rules which can match the empty string, such as
+    /// `x*`. The unvalidated [`MultiReplacerBuilder::build`] always accepts empty
+    /// patterns.
+    pub fn allow_empty_patterns(&mut self, yes: bool) -> &mut Self {
+        self.allow_empty = yes;
+        self
+    }
+
+    /// Compiles `pats` into a replacer with the options of this builder.
+    ///
+    /// Patterns are not validated: empty patterns are allowed, and a pattern
+    /// which matches the same text as an earlier one never wins. Use
+    /// [`MultiReplacerBuilder::try_build`] to reject them instead.
+    pub fn build<P, R>(&self, pats: &[(P, R)]) -> MultiReplacer
+    where
+        P: AsRef<str>,
+        R: AsRef<str>,
+    {
+        let engine = Engine::new(pats.iter().map(|(pat, _)| pat.as_ref()), &self.options);
+        let replacements = pats
+            .iter()
+            .map(|(_, new)| new.as_ref().to_owned())
+            .collect();
+
+        MultiReplacer {
+            engine,
+            replacements,
+        }
+    }
+
+    /// Same as [`MultiReplacerBuilder::build`], but checks the patterns first.
+    ///
+    /// Two patterns are the same if they match the same text under the case
+    /// sensitivity of this builder, so `hana` and `Hana` conflict when matching
+    /// case-insensitively.
+    ///
+    /// ```
+    /// use multirep::{MultiReplacer, MultirepError};
+    ///
+    /// let err = MultiReplacer::try_new(&[("Hana", "Minami"), ("Hana", "Sora")]).unwrap_err();
+    /// assert!(matches!(err, MultirepError::ConflictingReplacements { first: 0, index: 1 }));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MultirepError::EmptyPattern`] for an empty pattern unless
+    /// [allowed](MultiReplacerBuilder::allow_empty_patterns), and
+    /// [`MultirepError::DuplicatePattern`] or
+    /// [`MultirepError::ConflictingReplacements`] for a pattern which is the
+    /// same as an earlier one with the same or a different replacement.
+    pub fn try_build<P, R>(&self, pats: &[(P, R)]) -> Result<MultiReplacer, MultirepError>
+    where
+        P: AsRef<str>,
+        R: AsRef<str>,
+    {
+        self.validate(
+            pats.iter()
+                .map(|(pat, new)| (pat.as_ref().as_bytes(), new.as_ref().as_bytes(), &[][..]))
+                .enumerate(),
+        )?;
+        Ok(self.build(pats))
+    }
+
+    /// Compiles `rules` into a replacer with the options of this builder.
+    ///
+    /// Unlike [`MultiReplacerBuilder::build`], the rules may mix literal and regex
+    /// patterns, and matches are resolved among all of them by the match kind.
+    /// A regex only takes part with the non-overlapping matches that
+    /// `Regex::find_iter` would report, and is case-insensitive whenever the
+    /// case sensitivity is not [`CaseSensitivity::Sensitive`].
+    ///
+    /// ```
+    /// # #[cfg(feature = "regex")] {
+    /// use multirep::{MultiReplacer, Rule};
+    ///
+    /// let rep = MultiReplacer::builder()
+    ///     .build_rules(&[
+    ///         Rule::literal("Hana", "Minami"),
+    ///         Rule::regex(r"(\w+) is cute", "$1 is kawaii"),
+    ///     ])
+    ///     .unwrap();
+    /// assert_eq!("Minami and Sora is kawaii", rep.replace("Hana and Sora is cute"));
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MultirepError::Regex`] if a regex fails to compile, and
+    /// [`MultirepError::EmptyPattern`] if one can match the empty string unless
+    /// [empty patterns are allowed](MultiReplacerBuilder::allow_empty_patterns).
+    /// Returns the same errors as [`MultiReplacerBuilder::try_build`] for the
+    /// literal patterns.
+    pub fn build_rules(&self, rules: &[Rule]) -> Result<MultiReplacer, MultirepError> {
+        self.validate(rules.iter().enumerate().filter_map(|(i, rule)| {
+            let pat = rule.pattern().as_literal()?;
+            Some((
+                i,
+                (
+                    pat.as_bytes(),
+                    rule.replacement().as_bytes(),
+                    rule.contexts(),
+                ),
+            ))
+        }))?;
+        let engine = Engine::from_rules(rules, &self.options)?;
+        #[cfg(feature = "regex")]
+        if !self.allow_empty {
+            if let Some(index) = engine.empty_regex() {
+                return Err(MultirepError::EmptyPattern { index });
+            }
+        }
+        let replacements = rules
+            .iter()
+            .map(|rule| rule.replacement().to_owned())
+            .collect();
+
+        Ok(MultiReplacer {
+            engine,
+            replacements,
+        })
+    }
+
+    /// Compiles byte patterns into a [`BytesMultiReplacer`] with the options of this builder.
+    pub fn build_bytes<P, R>(&self, pats: &[(P, R)]) -> BytesMultiReplacer
+    where
+        P: AsRef<[u8]>,
+        R: AsRef<[u8]>,
+    {
+        let engine = Engine::new(pats.iter().map(|(pat, _)| pat.as_ref()), &self.options);
+        let replacements = pats
+            .iter()
+            .map(|(_, new)| new.as_ref().to_owned())
+            .collect();
+
+        BytesMultiReplacer {
+            engine,
+            replacements,
+        }
+    }
+
+    /// Same as [`MultiReplacerBuilder::build_bytes`], but checks the patterns
+    /// like [`MultiReplacerBuilder::try_build`].
+    ///
+    /// # Errors
+    ///
+    /// See [`MultiReplacerBuilder::try_build`].
+    pub fn try_build_bytes<P, R>(
+        &self,
+        pats: &[(P, R)],
+    ) -> Result<BytesMultiReplacer, MultirepError>
+    where
+        P: AsRef<[u8]>,
+        R: AsRef<[u8]>,
+    {
+        self.validate(
+            pats.iter()
+                .map(|(pat, new)| (pat.as_ref(), new.as_ref(), &[][..]))
+                .enumerate(),
+        )?;
+        Ok(self.build_bytes(pats))
+    }
+
+    /// Compiles a replacer exchanging `a` and `b` with the options of this
+    /// builder, like [`exchange`](crate::exchange) does.
+    ///
+    /// The longer pattern comes first, so under the default [`MatchKind`] it wins
+    /// over the other one where they overlap.
+    ///
+    /// ```
+    /// use multirep::{CaseSensitivity, MultiReplacer};
+    ///
+    /// let rep = MultiReplacer::builder()
+    ///     .case_sensitivity(CaseSensitivity::Ascii)
+    ///     .build_exchange("Hina", "Hinata");
+    /// assert_eq!("Hina and Hinata", rep.replace("hinata and HINA"));
+    /// ```
+    pub fn build_exchange(&self, a: &str, b: &str) -> MultiReplacer {
+        self.build(&longer_first(a, b))
+    }
+
+    /// Same as [`MultiReplacerBuilder::build_exchange`], but for byte patterns.
+    pub fn build_exchange_bytes(&self, a: &[u8], b: &[u8]) -> BytesMultiReplacer {
+        self.build_bytes(&longer_first(a, b))
+    }
+
+    /// Checks `(index, (pattern, replacement, contexts))` for empty and repeated
+    /// patterns.
+    fn validate<'p>(
+        &self,
+        pats: impl IntoIterator<Item = (usize, (&'p [u8], &'p [u8], &'p [Context]))>,
+    ) -> Result<(), MultirepError> {
+        let mut seen = HashMap::new();
+
+        for (index, (pat, new, contexts)) in pats {
+            if pat.is_empty() && !self.allow_empty {
+                return Err(MultirepError::EmptyPattern { index });
+            }
+            // patterns are the same if they match the same text in the same context
+            let text = match self.options.case {
+                CaseSensitivity::Sensitive => Cow::Borrowed(pat),
+                CaseSensitivity::Ascii => Cow::Owned(pat.to_ascii_lowercase()),
+                CaseSensitivity::Unicode => Cow::Owned(Folded::new(pat).text),
+            };
+            let key = (text, contexts);
+            match seen.entry(key) {
+                Entry::Occupied(e) => {
+                    let (first, old) = *e.get();
+                    return Err(if old == new {
+                        MultirepError::DuplicatePattern { first, index }
+                    } else {
+                        MultirepError::ConflictingReplacements { first, index }
+                    });
+                }
+                Entry::Vacant(e) => {
+                    e.insert((index, new));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Orders the pair of patterns to exchange so that the longer one wins.
+fn longer_first<T: AsRef<[u8]> + Copy>(a: T, b: T) -> [(T, T); 2] {
+    // if a contains b, searching b first will also match a substring of a.
+    // search the longer one to avoid such a situation.
+    if a.as_ref().len() > b.as_ref().len() {
+        [(a, b), (b, a)]
+    } else {
+        [(b, a), (a, b)]
+    }
+}
+
+/// A compiled set of patterns which can be applied to many strings.
+///
+/// Building a `MultiReplacer` compiles all patterns into a single automaton,
+/// so each call to [`MultiReplacer::replace`] scans the haystack only once
+/// no matter how many patterns there are.
+///
+/// A match is only accepted if it doesn't overlap any match which wins over it
+/// according to the [`MatchKind`], so replaced spans never overlap each other.
+/// Building one panics for patterns too large to compile, see
+/// [`MultiReplacerBuilder`](MultiReplacerBuilder#panics).
+///
+/// ```
+/// use multirep::MultiReplacer;
+///
+/// let rep = MultiReplacer::new(&[("Hana", "Minami"), ("cute", "kawaii")]);
+/// assert_eq!("Minami is kawaii", rep.replace("Hana is cute"));
+/// assert_eq!("Minami is not kawaii", rep.replace("Hana is not cute"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiReplacer {
+    engine: Engine,
+    replacements: Vec<String>,
+}
+
+impl MultiReplacer {
+    /// Compiles `pats` into a replacer.
+    ///
+    /// Patterns earlier in `pats` take precedence over later ones, exactly as in
+    /// [`multi_replace`](crate::multi_replace).
+    pub fn new<P, R>(pats: &[(P, R)]) -> Self
+    where
+        P: AsRef<str>,
+        R: AsRef<str>,
+    {
+        MultiReplacerBuilder::new().build(pats)
+    }
+
+    /// Same as [`MultiReplacer::new`], but rejects empty and repeated patterns.
+    ///
+    /// # Errors
+    ///
+    /// See [`MultiReplacerBuilder::try_build`].
+    pub fn try_new<P, R>(pats: &[(P, R)]) -> Result<Self, MultirepError>
+    where
+        P: AsRef<str>,
+        R: AsRef<str>,
+    {
+        MultiReplacerBuilder::new().try_build(pats)
+    }
+
+    /// Compiles a replacer exchanging `a` and `b`, exactly as in
+    /// [`exchange`](crate::exchange).
+    pub fn exchange(a: &str, b: &str) -> Self {
+        MultiReplacerBuilder::new().build_exchange(a, b)
+    }
+
+    /// Creates a [`MultiReplacerBuilder`] to configure a replacer.
+    pub fn builder() -> MultiReplacerBuilder {
+        MultiReplacerBuilder::new()
+    }
+
+    /// Returns the number of patterns in this replacer.
+    pub fn patterns_len(&self) -> usize {
+        self.replacements.len()
+    }
+
+    /// Returns how overlapping matches are resolved by this replacer.
+    pub fn match_kind(&self) -> MatchKind {
+        self.engine.kind()
+    }
+
+    /// Returns the matches that [`MultiReplacer::replace`] would replace, ordered by position.
+    ///
+    /// ```
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::new(&[("Hana", "Minami"), ("na", "no")]);
+    /// let found: Vec<_> = rep.find_iter("Hana and Hinata").map(|m| m.range()).collect();
+    /// assert_eq!(vec![0..4, 11..13], found);
+    /// ```
+    pub fn find_iter<'h>(&self, s: &'h str) -> FindIter<'h> {
+        FindIter::new(s, self.engine.find(s.as_bytes(), true))
+    }
+
+    /// Checks whether [`MultiReplacer::replace`] would replace anything in `s`.
+    ///
+    /// This stops searching at the first occurrence of any pattern.
+    pub fn is_match(&self, s: &str) -> bool {
+        self.engine.is_match(s.as_bytes(), true)
+    }
+
+    /// Returns the number of matches [`MultiReplacer::replace`] would replace in `s`.
+    pub fn count(&self, s: &str) -> usize {
+        if !self.is_match(s) {
+            return 0;
+        }
+        self.engine.find(s.as_bytes(), true).len()
+    }
+
+    /// Replaces all patterns in `s` in a single pass.
+    pub fn replace(&self, s: &str) -> String {
+        self.replace_cow(s).into_owned()
+    }
+
+    /// Same as [`MultiReplacer::replace`], but borrows `s` if nothing is replaced.
+    ///
+    /// Otherwise, the output is allocated once with its exact length.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::new(&[("cute", "kawaii")]);
+    /// assert!(matches!(rep.replace_cow("Hana is cool"), Cow::Borrowed("Hana is cool")));
+    /// assert_eq!("Hana is kawaii", rep.replace_cow("Hana is cute"));
+    /// ```
+    pub fn replace_cow<'h>(&self, s: &'h str) -> Cow<'h, str> {
+        let matches = self.engine.find(s.as_bytes(), true);
+        self.replace_matches(s, &matches)
+    }
+
+    /// Keeps replacing all patterns until the text stops changing, and returns
+    /// it together with the number of passes which changed it.
+    ///
+    /// Unlike [`MultiReplacer::replace`], the output of a pass is searched again,
+    /// so replacements may expand into text which is replaced in turn. Each pass
+    /// resolves its matches as usual.
+    ///
+    /// ```
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::new(&[("$NAME", "$FIRST $LAST"), ("$FIRST", "Hana"), ("$LAST", "Sato")]);
+    /// let (r, passes) = rep.replace_cascading("Hi, $NAME!", 10).unwrap();
+    /// assert_eq!("Hi, Hana Sato!", r);
+    /// assert_eq!(2, passes);
+    ///
+    /// let swap = MultiReplacer::new(&[("Hana", "Minami"), ("Minami", "Hana")]);
+    /// assert!(swap.replace_cascading("Hana", 10).is_err());
+    /// ```
+    ///
+    /// All texts produced so far are kept to detect cycles, so `max_passes` also
+    /// bounds the memory used.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MultirepError::Cycle`] with the patterns replaced along the way if
+    /// a pass produces a text seen before, and [`MultirepError::TooManyPasses`] if
+    /// the text still changes after `max_passes` passes.
+    pub fn replace_cascading(
+        &self,
+        s: &str,
+        max_passes: usize,
+    ) -> Result<(String, usize), MultirepError> {
+        // the pass after which each text was seen
+        let mut seen = HashMap::new();
+        // the patterns replaced in each pass
+        let mut replaced = Vec::new();
+        let mut text = s.to_owned();
+        let mut pass = 0;
+
+        loop {
+            let matches = self.engine.find(text.as_bytes(), true);
+            let new = match self.replace_matches(&text, &matches) {
+                Cow::Owned(new) if new != text => new,
+                _ => return Ok((text, pass)),
+            };
+            if pass == max_passes {
+                return Err(MultirepError::TooManyPasses { max_passes });
+            }
+
+            replaced.push(matches.into_iter().map(|(_, _, pat)| pat));
+            if let Some(&first) = seen.get(&new) {
+                let mut rules: Vec<_> = replaced.drain(first..).flatten().collect();
+                rules.sort_unstable();
+                rules.dedup();
+                return Err(MultirepError::Cycle { rules });
+            }
+            seen.insert(std::mem::replace(&mut text, new), pass);
+            pass += 1;
+        }
+    }
+
+    /// Replaces `matches`, which were found in `s`.
+    fn replace_matches<'h>(&self, s: &'h str, matches: &[(usize, usize, usize)]) -> Cow<'h, str> {
+        if matches.is_empty() {
+            return Cow::Borrowed(s);
+        }
+
+        let mut news = self.replacements_for(s, matches);
+        let len = matches
+            .iter()
+            .zip(&news)
+            .fold(s.len(), |len, (&(_, l, _), new)| len - l + new.len());
+        let mut result = String::with_capacity(len);
+        let mut news = news.drain(..);
+        splice_str(&mut result, s, matches, |_| news.next().unwrap());
+        Cow::Owned(result)
+    }
+
+    /// Same as [`MultiReplacer::replace`], but only accepts matches which lie
+    /// within one of the byte `ranges` of `s`.
+    ///
+    /// A match crossing the edge of a range is rejected, and doesn't shadow other
+    /// matches. Ranges may be given in any order and may overlap; ranges which
+    /// only touch are separate, so no match spans both. Text outside the ranges is
+    /// still seen as context for word boundaries and rule contexts.
+    ///
+    /// ```
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::new(&[("Hana", "Minami")]);
+    /// let s = "Hana, 'Hana', Hana";
+    /// assert_eq!("Hana, 'Minami', Hana", rep.replace_in_ranges(s, &[6..12]));
+    /// assert_eq!("Hana, 'Hana', Hana", rep.replace_in_ranges(s, &[6..9]));
+    /// ```
+    pub fn replace_in_ranges(&self, s: &str, ranges: &[Range<usize>]) -> String {
+        let ranges = Ranges::new(ranges);
+        self.replace_filtered(s, |start, end| ranges.contains(start, end))
+    }
+
+    /// Same as [`MultiReplacer::replace`], but rejects matches which share a byte
+    /// with one of the byte `ranges` of `s`, leaving the ranges untouched.
+    ///
+    /// Rejected matches don't shadow other matches. An empty match is only
+    /// rejected if it is strictly inside a range.
+    ///
+    /// ```
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::new(&[("Hana", "Minami")]);
+    /// let s = "Hana, 'Hana', Hana";
+    /// assert_eq!("Minami, 'Hana', Minami", rep.replace_excluding(s, &[6..12]));
+    /// assert_eq!("Minami, 'Hana', Minami", rep.replace_excluding(s, &[10..11]));
+    /// ```
+    pub fn replace_excluding(&self, s: &str, ranges: &[Range<usize>]) -> String {
+        let ranges = Ranges::new(ranges);
+        self.replace_filtered(s, |start, end| !ranges.intersects(start, end))
+    }
+
+    /// Replaces the matches among the occurrences at `start..end` for which `keep`
+    /// returns `true`.
+    fn replace_filtered(&self, s: &str, keep: impl Fn(usize, usize) -> bool) -> String {
+        let mut candidates = self.engine.candidates(s.as_bytes(), true);
+        candidates.retain(|&(i, len, _)| keep(i, i + len));
+        let matches = self.engine.resolve(candidates);
+        self.replace_matches(s, &matches).into_owned()
+    }
+
+    /// Same as [`MultiReplacer::replace`], but also reports what was replaced.
+    ///
+    /// ```
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::new(&[("Hana", "Minami"), ("na", "no")]);
+    /// let (r, report) = rep.replace_report("Hana is Hana");
+    /// assert_eq!("Minami is Minami", r);
+    /// assert_eq!(8..12, report.spans()[1].input);
+    /// assert_eq!(10..16, report.spans()[1].output);
+    /// assert_eq!(2, report.counts()[0].replaced);
+    /// assert_eq!(2, report.counts()[1].shadowed);
+    /// ```
+    pub fn replace_report(&self, s: &str) -> (String, ReplaceReport) {
+        let candidates = self.engine.candidates(s.as_bytes(), true);
+        let mut counts = vec![PatternCount::default(); self.patterns_len()];
+        for &(_, _, pat) in &candidates {
+            counts[pat].shadowed += 1;
+        }
+
+        let matches = self.engine.resolve(candidates);
+        let mut news = self.replacements_for(s, &matches);
+        let mut spans = Vec::with_capacity(matches.len());
+        let (mut removed, mut added) = (0, 0);
+        for (&(pos, len, pat), new) in matches.iter().zip(&news) {
+            counts[pat].shadowed -= 1;
+            counts[pat].replaced += 1;
+
+            let new_len = new.len();
+            let start = pos - removed + added;
+            spans.push(ReplacedSpan {
+                pattern: pat,
+                input: pos..pos + len,
+                output: start..start + new_len,
+            });
+            removed += len;
+            added += new_len;
+        }
+
+        let mut result = String::with_capacity(s.len() - removed + added);
+        let mut news = news.drain(..);
+        splice_str(&mut result, s, &matches, |_| news.next().unwrap());
+        (result, ReplaceReport::new(spans, counts))
+    }
+
+    /// Replaces each match in `s` with the result of calling `f` on it.
+    ///
+    /// The replacements given when building this replacer are ignored. `f` is
+    /// called once for each accepted match, in order of position.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::new(&[("Hana", ""), ("cute", "")]);
+    /// let r = rep.replace_with("Hana is cute", |m| Cow::Owned(m.as_str().to_uppercase()));
+    /// assert_eq!("HANA is CUTE", r);
+    /// ```
+    pub fn replace_with<'h, 'a, F>(&self, s: &'h str, f: F) -> String
+    where
+        F: FnMut(&Match<'h>) -> Cow<'a, str>,
+    {
+        let matches = self.engine.find(s.as_bytes(), true);
+        let mut result = String::with_capacity(s.len());
+        splice_str(&mut result, s, &matches, f);
+        result
+    }
+
+    /// Returns what [`MultiReplacer::replace`] replaces `m` with, where `m` was
+    /// found by [`MultiReplacer::find_iter`].
+    ///
+    /// This is useful to replace only some of the matches, together with
+    /// [`apply_edits`](crate::apply_edits).
+    ///
+    /// ```
+    /// use multirep::{apply_edits, CaseSensitivity, MultiReplacer};
+    ///
+    /// let s = "HANA and Hana";
+    /// let rep = MultiReplacer::builder()
+    ///     .case_sensitivity(CaseSensitivity::Ascii)
+    ///     .preserve_case(true)
+    ///     .build(&[("hana", "minami")]);
+    /// let m = rep.find_iter(s).last().unwrap();
+    /// assert_eq!("Minami", rep.replacement_of(&m));
+    /// let edits = [(m.range(), &*rep.replacement_of(&m))];
+    /// assert_eq!("HANA and Minami", apply_edits(s, edits).unwrap());
+    /// ```
+    pub fn replacement_of(&self, m: &Match<'_>) -> Cow<'_, str> {
+        let len = m.end() - m.start();
+        self.replacement(m.haystack().as_bytes(), (m.start(), len, m.pattern()))
+    }
+
+    /// Replaces all patterns in the data read from `reader` and writes the result to `writer`.
+    ///
+    /// For literal patterns, only a small tail of the input is buffered, which is
+    /// enough to find matches straddling the boundaries of reads. Overlapping
+    /// occurrences of patterns can't be split, so a long run of them (such as
+    /// `aaaa...` for the pattern `aa`) is buffered as a whole. A regex may match
+    /// any amount of text, so with regex rules the whole input is buffered.
+    ///
+    /// For valid UTF-8 input, the output is the same as [`MultiReplacer::replace`]
+    /// on the whole input.
+    ///
+    /// ```
+    /// use multirep::MultiReplacer;
+    ///
+    /// let rep = MultiReplacer::new(&[("Hana", "Minami"), ("cute", "kawaii")]);
+    /// let mut out = Vec::new();
+    /// rep.stream("Hana is cute".as_bytes(), &mut out).unwrap();
+    /// assert_eq!(b"Minami is kawaii", &out[..]);
+    /// ```
+    pub fn stream<R: Read, W: Write>(&self, reader: R, writer: W) -> io::Result<()> {
+        stream(
+            &self.engine,
+            true,
+            |haystack, m| match self.replacement(haystack, m) {
+                Cow::Borrowed(new) => Cow::Borrowed(new.as_bytes()),
+                Cow::Owned(new) => Cow::Owned(new.into_bytes()),
+            },
+            reader,
+            writer,
+        )
+    }
+
+    /// Returns the replacement of the match `(pos, len, pat)` in `haystack`.
+    ///
+    /// `haystack` is only invalid UTF-8 when streaming, in which case the case of
+    /// invalid matches is not preserved.
+    fn replacement(&self, haystack: &[u8], (pos, len, pat): (usize, usize, usize)) -> Cow<'_, str> {
+        #[allow(unused_mut)]
+        let mut new = Cow::Borrowed(self.replacements[pat].as_str());
+        #[cfg(feature = "regex")]
+        if let Some(expanded) = self.engine.expand(pat, haystack, pos, new.as_bytes()) {
+            new = Cow::Owned(match String::from_utf8(expanded) {
+                Ok(expanded) => expanded,
+                // captures of `(?-u)` regexes may split chars
+                Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            });
+        }
+
+        match std::str::from_utf8(&haystack[pos..pos + len]) {
+            Ok(matched) if self.engine.options().preserve_case => {
+                match preserve_case(matched, &new) {
+                    Cow::Owned(cased) => Cow::Owned(cased),
+                    Cow::Borrowed(_) => new,
+                }
+            }
+            _ => new,
+        }
+    }
+
+    fn replacements_for(&self, s: &str, matches: &[(usize, usize, usize)]) -> Vec<Cow<'_, str>> {
+        matches
+            .iter()
+            .map(|&m| self.replacement(s.as_bytes(), m))
+            .collect()
+    }
+}
+
+/// A compiled set of byte patterns which can be applied to data that is not
+/// necessarily valid UTF-8.
+///
+/// It resolves matches the same way as [`MultiReplacer`].
+///
+/// ```
+/// use multirep::MultiReplacer;
+///
+/// let rep = MultiReplacer::builder().build_bytes(&[(b"\xff", b"?")]);
+/// assert_eq!(b"Hana?", &rep.replace(b"Hana\xff")[..]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BytesMultiReplacer {
+    engine: Engine,
+    replacements: Vec<Vec<u8>>,
+}
+
+impl BytesMultiReplacer {
+    /// Compiles `pats` into a replacer.
+    ///
+    /// Patterns earlier in `pats` take precedence over later ones, exactly as in
+    /// [`multi_replace_bytes`](crate::multi_replace_bytes).
+    pub fn new<P, R>(pats: &[(P, R)]) -> Self
+    where
+        P: AsRef<[u8]>,
+        R: AsRef<[u8]>,
+    {
+        MultiReplacerBuilder::new().build_bytes(pats)
+    }
+
+    /// Compiles a replacer exchanging `a` and `b`, exactly as in
+    /// [`exchange_bytes`](crate::exchange_bytes).
+    pub fn exchange(a: &[u8], b: &[u8]) -> Self {
+        MultiReplacerBuilder::new().build_exchange_bytes(a, b)
+    }
+
+    /// Returns the number of patterns in this replacer.
+    pub fn patterns_len(&self) -> usize {
+        self.replacements.len()
+    }
+
+    /// Returns how overlapping matches are resolved by this replacer.
+    pub fn match_kind(&self) -> MatchKind {
+        self.engine.kind()
+    }
+
+    /// Replaces all patterns in `s` in a single pass.
+    pub fn replace(&self, s: &[u8]) -> Vec<u8> {
+        self.replace_cow(s).into_owned()
+    }
+
+    /// Same as [`BytesMultiReplacer::replace`], but borrows `s` if nothing is replaced.
+    ///
+    /// Otherwise, the output is allocated once with its exact length.
+    pub fn replace_cow<'h>(&self, s: &'h [u8]) -> Cow<'h, [u8]> {
+        let matches = self.engine.find(s, false);
+        if matches.is_empty() {
+            return Cow::Borrowed(s);
+        }
+        Cow::Owned(splice(s, &matches, |s, (pos, len, pat)| {
+            self.replacement(pat, &s[pos..pos + len])
+        }))
+    }
+
+    /// Replaces all patterns in the data read from `reader` and writes the result to `writer`.
+    ///
+    /// See [`MultiReplacer::stream`] for details.
+    pub fn stream<R: Read, W: Write>(&self, reader: R, writer: W) -> io::Result<()> {
+        stream(
+            &self.engine,
+            false,
+            |s, (pos, len, pat)| self.replacement(pat, &s[pos..pos + len]),
+            reader,
+            writer,
+        )
+    }
+
+    /// Returns the replacement of a match of pattern `pat`.
+    fn replacement(&self, pat: usize, matched: &[u8]) -> Cow<'_, [u8]> {
+        let new = &self.replacements[pat];
+        if self.engine.options().preserve_case {
+            if let (Ok(matched), Ok(new)) = (std::str::from_utf8(matched), std::str::from_utf8(new))
+            {
+                if let Cow::Owned(new) = preserve_case(matched, new) {
+                    return Cow::Owned(new.into_bytes());
+                }
+            }
+        }
+        Cow::Borrowed(new)
+    }
+}
+
+/// Replaces each match in `s` with the result of calling `f` on it, appending the
+/// result to `out`.
+///
+/// `matches` must be ordered by position and must not overlap.
+fn splice_str<'h, 'a>(
+    out: &mut String,
+    s: &'h str,
+    matches: &[(usize, usize, usize)],
+    mut f: impl FnMut(&Match<'h>) -> Cow<'a, str>,
+) {
+    // pos and `pos + len` are ends of a match of a `str` pattern, and empty matches
+    // are filtered to char boundaries, so slicing never panics
+    edit::splice_str(
+        out,
+        s,
+        matches.iter().map(|&(pos, len, pat)| {
+            let m = Match::new(s, pos, pos + len, pat);
+            (m.range(), f(&m))
+        }),
+    );
+}
@@ -0,0 +1,49 @@
+use std::ops::Range;
+
+/// What was replaced by [`MultiReplacer::replace_report`](crate::MultiReplacer::replace_report).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplaceReport {
+    spans: Vec<ReplacedSpan>,
+    counts: Vec<PatternCount>,
+}
+
+/// A replaced match.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ReplacedSpan {
+    /// Index of the matched pattern.
+    pub pattern: usize,
+    /// Byte range of the match in the input.
+    pub input: Range<usize>,
+    /// Byte range of the replacement in the output.
+    pub output: Range<usize>,
+}
+
+/// How often a pattern occurred.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PatternCount {
+    /// Number of occurrences which were replaced.
+    pub replaced: usize,
+    /// Number of occurrences which were not replaced because another match won over them.
+    pub shadowed: usize,
+}
+
+impl ReplaceReport {
+    pub(crate) fn new(spans: Vec<ReplacedSpan>, counts: Vec<PatternCount>) -> Self {
+        Self { spans, counts }
+    }
+
+    /// Returns the replaced matches, ordered by position.
+    pub fn spans(&self) -> &[ReplacedSpan] {
+        &self.spans
+    }
+
+    /// Returns the counts of each pattern, indexed by pattern.
+    pub fn counts(&self) -> &[PatternCount] {
+        &self.counts
+    }
+
+    /// Returns the total number of replaced matches.
+    pub fn replaced(&self) -> usize {
+        self.spans.len()
+    }
+}
@@ -0,0 +1,323 @@
+use crate::engine::{CaseSensitivity, MatchKind};
+use crate::error::MultirepError;
+use crate::replacer::{MultiReplacer, MultiReplacerBuilder};
+use crate::word::WordBoundary;
+
+/// What a [`Rule`] matches.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Pattern {
+    /// Matches the string itself.
+    Literal(String),
+    /// Matches a regex, see the [`regex`](https://docs.rs/regex) crate for the syntax.
+    #[cfg(feature = "regex")]
+    Regex(String),
+}
+
+impl Pattern {
+    /// Returns the string of a literal pattern.
+    pub(crate) fn as_literal(&self) -> Option<&str> {
+        match self {
+            Pattern::Literal(pat) => Some(pat),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(_) => None,
+        }
+    }
+}
+
+/// Literal text which has to be, or must not be, right next to a match.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Context {
+    pub(crate) text: String,
+    /// Whether `text` follows the match instead of preceding it.
+    pub(crate) after: bool,
+    /// Whether the match is rejected if `text` is there.
+    pub(crate) negated: bool,
+}
+
+/// A pattern together with its replacement.
+///
+/// With the `serde` feature, a rule is (de)serialized as a map with the pattern
+/// under `from` (or `regex`), the replacement under `to`, and lists of contexts
+/// under `preceded-by`, `not-preceded-by`, `followed-by` and `not-followed-by`.
+///
+/// Literal and regex rules can be mixed with
+/// [`MultiReplacerBuilder::build_rules`](crate::MultiReplacerBuilder::build_rules).
+///
+/// A rule can also require text before or after its matches. The context is
+/// looked up in the original input, under the same case sensitivity as the
+/// patterns, and is not part of the match. Occurrences without the required
+/// context are dropped before overlapping matches are resolved, so they never
+/// shadow other matches.
+///
+/// ```
+/// use multirep::{MultiReplacer, Rule};
+///
+/// let rep = MultiReplacer::builder()
+///     .build_rules(&[
+///         Rule::literal("cute", "kawaii").preceded_by("is "),
+///         Rule::literal("Hana", "Minami").not_followed_by("ko"),
+///     ])
+///     .unwrap();
+/// assert_eq!("Minami is kawaii, Hanako is not cute", rep.replace("Hana is cute, Hanako is not cute"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RuleDef", into = "RuleDef"))]
+pub struct Rule {
+    pattern: Pattern,
+    replacement: String,
+    contexts: Vec<Context>,
+}
+
+impl Rule {
+    /// Creates a rule replacing `pattern` with `replacement`.
+    pub fn literal(pattern: impl Into<String>, replacement: impl Into<String>) -> Self {
+        Self {
+            pattern: Pattern::Literal(pattern.into()),
+            replacement: replacement.into(),
+            contexts: Vec::new(),
+        }
+    }
+
+    /// Creates a rule replacing matches of the regex `pattern` with `replacement`.
+    ///
+    /// In `replacement`, `$1` or `${name}` is replaced with the text of a capture
+    /// group and `$$` with a literal `$`, as in `regex::Captures::expand`.
+    ///
+    /// The regex has full Unicode support, so `.` and classes such as `\w` match
+    /// non-ASCII chars. With [`CaseSensitivity::Ascii`], only ASCII letters match
+    /// regardless of their case, like in literal patterns.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: impl Into<String>, replacement: impl Into<String>) -> Self {
+        Self {
+            pattern: Pattern::Regex(pattern.into()),
+            replacement: replacement.into(),
+            contexts: Vec::new(),
+        }
+    }
+
+    /// Only matches where `text` comes right before the match.
+    pub fn preceded_by(self, text: impl Into<String>) -> Self {
+        self.context(text.into(), false, false)
+    }
+
+    /// Only matches where `text` does not come right before the match.
+    pub fn not_preceded_by(self, text: impl Into<String>) -> Self {
+        self.context(text.into(), false, true)
+    }
+
+    /// Only matches where `text` comes right after the match.
+    pub fn followed_by(self, text: impl Into<String>) -> Self {
+        self.context(text.into(), true, false)
+    }
+
+    /// Only matches where `text` does not come right after the match.
+    pub fn not_followed_by(self, text: impl Into<String>) -> Self {
+        self.context(text.into(), true, true)
+    }
+
+    fn context(mut self, text: String, after: bool, negated: bool) -> Self {
+        self.contexts.push(Context {
+            text,
+            after,
+            negated,
+        });
+        self
+    }
+
+    /// Returns what this rule matches.
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    /// Returns the replacement of this rule.
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+
+    /// Returns the context constraints of this rule, which all have to hold.
+    pub(crate) fn contexts(&self) -> &[Context] {
+        &self.contexts
+    }
+}
+
+impl<P: Into<String>, R: Into<String>> From<(P, R)> for Rule {
+    fn from((pattern, replacement): (P, R)) -> Self {
+        Self::literal(pattern, replacement)
+    }
+}
+
+/// An owned list of rules together with the options to compile them with, as
+/// loaded from a configuration file.
+///
+/// With the `serde` feature, it can be (de)serialized with any serde format.
+/// Options are optional and use kebab-case names:
+///
+/// ```
+/// # #[cfg(feature = "serde")] {
+/// use multirep::RuleSet;
+///
+/// let rules: RuleSet = toml::from_str(r#"
+///     match-kind = "leftmost-longest"
+///     case-sensitivity = "ascii"
+///     preserve-case = true
+///
+///     [[rules]]
+///     from = "hana"
+///     to = "minami"
+///
+///     [[rules]]
+///     from = "cute"
+///     to = "kawaii"
+///     preceded-by = ["is "]
+/// "#).unwrap();
+/// let rep = rules.build().unwrap();
+/// assert_eq!("Minami is kawaii, cute", rep.replace("Hana is cute, cute"));
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub struct RuleSet {
+    /// The rules, earlier ones taking precedence as in [`MultiReplacerBuilder::build_rules`].
+    pub rules: Vec<Rule>,
+    /// See [`MultiReplacerBuilder::match_kind`].
+    pub match_kind: MatchKind,
+    /// See [`MultiReplacerBuilder::case_sensitivity`].
+    pub case_sensitivity: CaseSensitivity,
+    /// See [`MultiReplacerBuilder::word_boundary`].
+    pub word_boundary: WordBoundary,
+    /// See [`MultiReplacerBuilder::preserve_case`].
+    pub preserve_case: bool,
+    /// See [`MultiReplacerBuilder::allow_empty_patterns`].
+    pub allow_empty_patterns: bool,
+}
+
+impl RuleSet {
+    /// Creates an empty rule set with default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a builder with the options of this rule set.
+    pub fn builder(&self) -> MultiReplacerBuilder {
+        let mut builder = MultiReplacerBuilder::new();
+        builder
+            .match_kind(self.match_kind)
+            .case_sensitivity(self.case_sensitivity)
+            .word_boundary(self.word_boundary)
+            .preserve_case(self.preserve_case)
+            .allow_empty_patterns(self.allow_empty_patterns);
+        builder
+    }
+
+    /// Compiles the rules into a replacer with the options of this rule set.
+    ///
+    /// # Errors
+    ///
+    /// See [`MultiReplacerBuilder::build_rules`].
+    pub fn build(&self) -> Result<MultiReplacer, MultirepError> {
+        self.builder().build_rules(&self.rules)
+    }
+}
+
+impl From<Vec<Rule>> for RuleSet {
+    fn from(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
+    }
+}
+
+impl FromIterator<Rule> for RuleSet {
+    fn from_iter<I: IntoIterator<Item = Rule>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl Extend<Rule> for RuleSet {
+    fn extend<I: IntoIterator<Item = Rule>>(&mut self, iter: I) {
+        self.rules.extend(iter);
+    }
+}
+
+/// The serialized form of a [`Rule`].
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct RuleDef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regex: Option<String>,
+    to: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    preceded_by: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    not_preceded_by: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    followed_by: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    not_followed_by: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RuleDef> for Rule {
+    type Error = String;
+
+    fn try_from(def: RuleDef) -> Result<Self, Self::Error> {
+        let mut rule = match (def.from, def.regex) {
+            (Some(from), None) => Rule::literal(from, def.to),
+            #[cfg(feature = "regex")]
+            (None, Some(regex)) => Rule::regex(regex, def.to),
+            #[cfg(not(feature = "regex"))]
+            (None, Some(_)) => return Err("regex rules need the `regex` feature".to_owned()),
+            _ => return Err("a rule needs exactly one of `from` and `regex`".to_owned()),
+        };
+        let contexts = [
+            (def.preceded_by, false, false),
+            (def.not_preceded_by, false, true),
+            (def.followed_by, true, false),
+            (def.not_followed_by, true, true),
+        ];
+        for (texts, after, negated) in contexts {
+            for text in texts {
+                rule = rule.context(text, after, negated);
+            }
+        }
+        Ok(rule)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Rule> for RuleDef {
+    fn from(rule: Rule) -> Self {
+        let (from, regex) = match rule.pattern {
+            Pattern::Literal(pat) => (Some(pat), None),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(pat) => (None, Some(pat)),
+        };
+        let mut def = RuleDef {
+            from,
+            regex,
+            to: rule.replacement,
+            preceded_by: Vec::new(),
+            not_preceded_by: Vec::new(),
+            followed_by: Vec::new(),
+            not_followed_by: Vec::new(),
+        };
+        for ctx in rule.contexts {
+            let texts = match (ctx.after, ctx.negated) {
+                (false, false) => &mut def.preceded_by,
+                (false, true) => &mut def.not_preceded_by,
+                (true, false) => &mut def.followed_by,
+                (true, true) => &mut def.not_followed_by,
+            };
+            texts.push(ctx.text);
+        }
+        def
+    }
+}
@@ -0,0 +1,197 @@
+use std::borrow::Cow;
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::engine::{splice, Engine};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Copies `reader` to `writer`, replacing matches on the way.
+///
+/// Only the tail of the input which may still take part in a match is kept in
+/// memory, together with a little context before it to check word boundaries.
+/// The output is the same as replacing the whole input at once.
+///
+/// Regex matches have no length limit, so no part of the input is ever known to
+/// be done with; with regexes the whole input is read and then searched once.
+pub(crate) fn stream<'a, R, W>(
+    engine: &Engine,
+    utf8: bool,
+    replacement: impl Fn(&[u8], (usize, usize, usize)) -> Cow<'a, [u8]>,
+    mut reader: R,
+    mut writer: W,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    let context = engine.context_len();
+    let lookahead = engine.max_pattern_len().saturating_add(context);
+    if lookahead == usize::MAX {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        let matches = engine.find(&buf, utf8);
+        writer.write_all(&splice(&buf, &matches, &replacement))?;
+        return writer.flush();
+    }
+
+    let mut buf = Vec::new();
+    // `buf[..written]` has already been written and is only kept as context
+    let mut written = 0;
+    let mut chunk = vec![0; CHUNK_SIZE];
+
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let eof = n == 0;
+        buf.extend_from_slice(&chunk[..n]);
+
+        let mut candidates = engine.candidates(&buf, utf8);
+        candidates.retain(|&(i, _, _)| i >= written);
+        let cut = if eof {
+            buf.len()
+        } else {
+            match safe_cut(&candidates, written, buf.len(), lookahead) {
+                Some(cut) => cut,
+                None => continue,
+            }
+        };
+
+        // nothing crosses `cut`, so matches before it are the same as if
+        // the whole input was searched
+        let mut matches = engine.resolve(candidates);
+        matches.retain(|&(i, _, _)| i < cut || eof);
+        for (i, _, _) in &mut matches {
+            *i -= written;
+        }
+        writer.write_all(&splice(&buf[written..cut], &matches, &replacement))?;
+        if eof {
+            return writer.flush();
+        }
+
+        let keep = cut.saturating_sub(context);
+        buf.drain(..keep);
+        written = cut - keep;
+    }
+}
+
+/// Finds the last position in `written..len` of the buffer where the input can be
+/// split without changing which matches are accepted.
+///
+/// Every occurrence starting before such a position must be complete in the
+/// buffer, must not extend past it, and must have `lookahead` bytes after its
+/// start to check whether it can be a match.
+fn safe_cut(
+    candidates: &[(usize, usize, usize)],
+    written: usize,
+    len: usize,
+    lookahead: usize,
+) -> Option<usize> {
+    // occurrences starting before `limit` are all known
+    let limit = (len + 1).checked_sub(lookahead.max(1))?;
+
+    let mut spans: Vec<_> = candidates
+        .iter()
+        .map(|&(i, len, _)| (i, i + len))
+        .filter(|&(start, end)| start < limit && end > start + 1)
+        .collect();
+    spans.sort_unstable();
+
+    // merge spans which share a position strictly inside them
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in spans {
+        match groups.last_mut() {
+            Some(last) if start < last.1 => last.1 = last.1.max(end),
+            _ => groups.push((start, end)),
+        }
+    }
+
+    // only the last group can contain `limit`, since all of them start before it
+    let cut = match groups.last() {
+        Some(&(start, end)) if limit < end => start,
+        _ => limit,
+    };
+    (cut > written).then_some(cut)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use crate::{MatchKind, MultiReplacer};
+
+    /// Reads at most `n` bytes at a time.
+    struct Chunked<'a>(&'a [u8], usize);
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.1.min(buf.len()).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn check(kind: MatchKind, s: &str, pats: &[(&str, &str)]) {
+        let rep = MultiReplacer::builder().match_kind(kind).build(pats);
+        let expected = rep.replace(s);
+        for n in [1, 2, 3, 7, 8192] {
+            let mut out = Vec::new();
+            rep.stream(Chunked(s.as_bytes(), n), &mut out).unwrap();
+            assert_eq!(expected, String::from_utf8(out).unwrap(), "{kind:?} {n}");
+        }
+    }
+
+    #[test]
+    fn same_as_replace() {
+        let kinds = [
+            MatchKind::Priority,
+            MatchKind::LeftmostFirst,
+            MatchKind::LeftmostLongest,
+            MatchKind::Rightmost,
+        ];
+        for kind in kinds {
+            check(
+                kind,
+                "Both Hana and Minami are cute, Hinata is cuter",
+                &[
+                    ("Hana", "Minami"),
+                    ("Minami", "Hana"),
+                    ("Hinata", "Hina"),
+                    ("cute", "kawaii"),
+                ],
+            );
+            check(
+                kind,
+                "abcabcabcbcab",
+                &[("bc", "X"), ("ab", "Y"), ("abcab", "Z")],
+            );
+            check(kind, "aaaaaaaaaaa", &[("aa", "b"), ("aaa", "c")]);
+            check(kind, "héhé", &[("", "-"), ("é", "e")]);
+            check(kind, "no match", &[("Hana", "Minami")]);
+            check(kind, "", &[("Hana", "Minami")]);
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_rules() {
+        use crate::{Rule, RuleSet};
+
+        let rep = RuleSet::from(vec![
+            Rule::literal("Hana", "Minami"),
+            Rule::regex(r"(\w+) is cute", "$1 is kawaii"),
+        ])
+        .build()
+        .unwrap();
+        let s = "Hana is cute, Sora is cute\n".repeat(1000);
+        let expected = rep.replace(&s);
+        for n in [1, 7, 8192] {
+            let mut out = Vec::new();
+            rep.stream(Chunked(s.as_bytes(), n), &mut out).unwrap();
+            assert_eq!(expected, String::from_utf8(out).unwrap(), "{n}");
+        }
+    }
+}
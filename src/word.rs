@@ -0,0 +1,150 @@
+use std::cell::OnceCell;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Decides whether matches have to be whole words.
+///
+/// ```
+/// use multirep::{MultiReplacer, WordBoundary};
+///
+/// let replace = |word, s| {
+///     MultiReplacer::builder()
+///         .word_boundary(word)
+///         .build(&[("cute", "kawaii"), ("café", "kissa")])
+///         .replace(s)
+/// };
+/// let s = "cute, cuteness, execute, café, cafés";
+/// assert_eq!("kawaii, kawaiiness, exekawaii, kissa, kissas", replace(WordBoundary::Anywhere, s));
+/// assert_eq!("kawaii, cuteness, execute, kissa, kissas", replace(WordBoundary::Ascii, s));
+/// assert_eq!("kawaii, cuteness, execute, kissa, cafés", replace(WordBoundary::Unicode, s));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum WordBoundary {
+    /// Matches may start and end anywhere.
+    #[default]
+    Anywhere,
+    /// Like `\b` in ASCII mode: a match must not start right after, or end right
+    /// before, an ASCII letter, digit or `_` if its own first or last char is one
+    /// of them. Non-ASCII chars never belong to a word.
+    Ascii,
+    /// A match must start and end on word boundaries as defined by
+    /// [UAX #29](https://www.unicode.org/reports/tr29/#Word_Boundaries), which also
+    /// works for accented letters and CJK text.
+    Unicode,
+}
+
+/// Bytes of context around a match which are needed to find the UAX #29 boundaries
+/// next to it.
+///
+/// The rules only look at a few chars around a position, but may skip any number of
+/// combining marks, so this is an approximation for pathological texts.
+const UNICODE_CONTEXT: usize = 64;
+
+impl WordBoundary {
+    /// Returns how many bytes before and after a match have to be seen to check it.
+    pub(crate) fn context_len(self) -> usize {
+        match self {
+            WordBoundary::Anywhere => 0,
+            WordBoundary::Ascii => 1,
+            WordBoundary::Unicode => UNICODE_CONTEXT,
+        }
+    }
+}
+
+/// Checks whether matches in a haystack are whole words.
+pub(crate) struct Words<'h> {
+    haystack: &'h [u8],
+    mode: WordBoundary,
+    /// Sorted word boundaries, computed on first use in Unicode mode.
+    bounds: OnceCell<Vec<usize>>,
+}
+
+impl<'h> Words<'h> {
+    pub(crate) fn new(haystack: &'h [u8], mode: WordBoundary) -> Self {
+        Self {
+            haystack,
+            mode,
+            bounds: OnceCell::new(),
+        }
+    }
+
+    /// Checks whether `start..end` of the haystack can be a match.
+    pub(crate) fn is_whole(&self, start: usize, end: usize) -> bool {
+        match self.mode {
+            WordBoundary::Anywhere => true,
+            WordBoundary::Ascii => {
+                let is_word = |i: usize| {
+                    self.haystack
+                        .get(i)
+                        .is_some_and(|&b| b.is_ascii_alphanumeric() || b == b'_')
+                };
+                let splits = |i: usize| i == 0 || !(is_word(i - 1) && is_word(i));
+                splits(start) && splits(end)
+            }
+            WordBoundary::Unicode => {
+                let bounds = self.bounds.get_or_init(|| unicode_bounds(self.haystack));
+                bounds.binary_search(&start).is_ok() && bounds.binary_search(&end).is_ok()
+            }
+        }
+    }
+}
+
+/// Finds UAX #29 word boundaries. Bytes which are not valid UTF-8 are words of
+/// their own.
+fn unicode_bounds(haystack: &[u8]) -> Vec<usize> {
+    let mut bounds = vec![0];
+    let mut pos = 0;
+
+    for chunk in haystack.utf8_chunks() {
+        let valid = chunk.valid();
+        bounds.extend(
+            valid
+                .split_word_bound_indices()
+                .skip(1)
+                .map(|(i, _)| pos + i),
+        );
+        pos += valid.len();
+        if !valid.is_empty() {
+            bounds.push(pos);
+        }
+        for _ in chunk.invalid() {
+            pos += 1;
+            bounds.push(pos);
+        }
+    }
+    bounds
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ascii() {
+        let words = Words::new(b"is cute_ness!", WordBoundary::Ascii);
+
+        assert!(words.is_whole(0, 2));
+        assert!(words.is_whole(3, 12));
+        assert!(words.is_whole(12, 13));
+        assert!(!words.is_whole(3, 7));
+        assert!(!words.is_whole(4, 12));
+    }
+
+    #[test]
+    fn unicode() {
+        let s = "Hana wa kawaii. 花は可愛い";
+        let words = Words::new(s.as_bytes(), WordBoundary::Unicode);
+        let at = |w: &str| s.find(w).unwrap();
+
+        assert!(words.is_whole(at("kawaii"), at(".")));
+        assert!(!words.is_whole(at("kawaii"), at(".") - 1));
+        assert!(words.is_whole(at("花"), at("は")));
+        assert_eq!(vec![0, 1, 2, 3], unicode_bounds(b"\xff\xfe\xfd"));
+        assert_eq!(
+            vec![0, 1, 2, 3, 4, 5, 6],
+            unicode_bounds(b"a\xff\xfe\xfd b")
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, SystemTime};
+
+fn multirep(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_multirep"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str], stdin: &str) -> String {
+    let output = multirep(args, stdin);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn path(p: &Path) -> &str {
+    p.to_str().unwrap()
+}
+
+#[test]
+fn rules_from_args() {
+    assert_eq!(
+        "Minami is kawaii\n",
+        stdout(
+            &["-r", "Hana=Minami", "--rule", "cute=kawaii"],
+            "Hana is cute\n"
+        )
+    );
+    assert_eq!("a=b", stdout(&["-r", "x=a=b"], "x"));
+    assert_eq!("Hana is ", stdout(&["-r", "cute="], "Hana is cute"));
+}
+
+#[test]
+fn swap() {
+    assert_eq!(
+        "Both Hinata and Hina are kawaii",
+        stdout(
+            &["--swap", "Hina", "Hinata"],
+            "Both Hina and Hinata are kawaii"
+        )
+    );
+    assert_eq!("aab", stdout(&["--swap", "ab", "bcd"], "abcd"));
+}
+
+#[test]
+fn rules_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let toml = dir.path().join("rules.toml");
+    fs::write(
+        &toml,
+        r#"
+            case-sensitivity = "ascii"
+            preserve-case = true
+
+            [[rules]]
+            from = "hana"
+            to = "minami"
+        "#,
+    )
+    .unwrap();
+    let json = dir.path().join("rules.json");
+    fs::write(&json, r#"{"rules": [{"from": "cute", "to": "kawaii"}]}"#).unwrap();
+
+    assert_eq!(
+        "MINAMI is kawaii",
+        stdout(&["-f", path(&toml), "-r", "cute=kawaii"], "HANA is cute")
+    );
+    assert_eq!(
+        "Hana is kawaii",
+        stdout(&["--rules", path(&json)], "Hana is cute")
+    );
+    // rules from the file come first
+    assert_eq!(
+        "minami is",
+        stdout(&["-f", path(&toml), "-r", "ana is=X"], "hana is")
+    );
+}
+
+#[test]
+fn files() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.txt");
+    fs::write(&a, "Hana\n").unwrap();
+    fs::write(&b, b"cute\xff\n").unwrap();
+
+    let output = multirep(
+        &[
+            "-r",
+            "Hana=Minami",
+            "-r",
+            "cute=kawaii",
+            path(&a),
+            "-",
+            path(&b),
+        ],
+        "Hana is cute\n",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        &b"Minami\nMinami is kawaii\nkawaii\xff\n"[..],
+        &output.stdout[..]
+    );
+    // inputs are replaced separately
+    assert_eq!(
+        "Hana\nHana\n",
+        stdout(&["-r", "na\nHa=X", path(&a), path(&a)], "")
+    );
+}
+
+#[test]
+fn errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.txt");
+    let invalid = dir.path().join("rules.toml");
+    fs::write(&invalid, "[[rules]]\nto = 'x'").unwrap();
+
+    let output = multirep(&["-r", "a=b", path(&missing)], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing.txt"));
+
+    let output = multirep(&["-f", path(&invalid)], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rules.toml"));
+
+    assert!(!multirep(&["-r", "a=b", "-r", "a=c"], "").status.success());
+    assert!(!multirep(&["-r", "ab"], "").status.success());
+    assert!(!multirep(&[], "").status.success());
+    assert!(!multirep(&["--swap", "a", "b", "-r", "a=b"], "")
+        .status
+        .success());
+}
+
+#[test]
+fn in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let hana = dir.path().join("hana.txt");
+    let other = dir.path().join("other.txt");
+    fs::write(&hana, "Hana is cute\n").unwrap();
+    fs::write(&other, "Sora is cute\n").unwrap();
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+    for path in [&hana, &other] {
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+    }
+
+    let output = multirep(&["-i", "-r", "Hana=Minami", path(&hana), path(&other)], "");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!("Minami is cute\n", fs::read_to_string(&hana).unwrap());
+    assert_ne!(old, fs::metadata(&hana).unwrap().modified().unwrap());
+    // untouched files are not rewritten
+    assert_eq!(old, fs::metadata(&other).unwrap().modified().unwrap());
+    assert_eq!(2, fs::read_dir(dir.path()).unwrap().count());
+
+    let output = multirep(
+        &[
+            "--in-place",
+            "--backup",
+            "--keep-mtime",
+            "-r",
+            "cute=kawaii",
+            path(&other),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!("Sora is kawaii\n", fs::read_to_string(&other).unwrap());
+    assert_eq!(old, fs::metadata(&other).unwrap().modified().unwrap());
+    assert_eq!(
+        "Sora is cute\n",
+        fs::read_to_string(dir.path().join("other.txt.bak")).unwrap()
+    );
+
+    let output = multirep(&["-i", "--backup=.orig", "-r", "is=was", path(&hana)], "");
+    assert!(output.status.success());
+    assert_eq!(
+        "Minami is cute\n",
+        fs::read_to_string(dir.path().join("hana.txt.orig")).unwrap()
+    );
+
+    assert!(!multirep(&["-i", "-r", "a=b"], "").status.success());
+    assert!(!multirep(&["-i", "-r", "a=b", "-"], "").status.success());
+    assert!(!multirep(&["--backup", "-r", "a=b", path(&hana)], "")
+        .status
+        .success());
+}
+
+#[cfg(unix)]
+#[test]
+fn in_place_unix() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("hana.sh");
+    let link = dir.path().join("link.sh");
+    fs::write(&script, "echo Hana\n").unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o750)).unwrap();
+    symlink(&script, &link).unwrap();
+
+    let output = multirep(&["-i", "-r", "Hana=Minami", path(&link)], "");
+    assert!(output.status.success());
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!("echo Minami\n", fs::read_to_string(&script).unwrap());
+    let mode = fs::metadata(&script).unwrap().permissions().mode();
+    assert_eq!(0o750, mode & 0o777);
+}
+
+#[test]
+fn recursive() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = [
+        "a.rs",
+        "b.txt",
+        ".hidden.rs",
+        "sub/c.rs",
+        "ignored.rs",
+        "target/d.rs",
+        ".git/e.rs",
+        "vendor/f.rs",
+    ];
+    for file in files {
+        let path = dir.path().join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "Hana").unwrap();
+    }
+    fs::write(dir.path().join(".gitignore"), "ignored.rs\n").unwrap();
+    fs::write(dir.path().join("binary.rs"), "Hana\0").unwrap();
+    let read = |file: &str| fs::read_to_string(dir.path().join(file)).unwrap();
+
+    let output = multirep(
+        &[
+            "-R",
+            "-i",
+            "-r",
+            "Hana=Minami",
+            "--include",
+            "*.rs",
+            "--exclude",
+            "vendor/**",
+            path(dir.path()),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    for file in &files[..4] {
+        let expected = if *file == "b.txt" { "Hana" } else { "Minami" };
+        assert_eq!(expected, read(file), "{file}");
+    }
+    for file in &files[4..] {
+        assert_eq!("Hana", read(file), "{file}");
+    }
+    assert_eq!("Hana\0", read("binary.rs"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("binary.rs: skipping binary file"),
+        "{stderr}"
+    );
+
+    // the current directory by default
+    let output = Command::new(env!("CARGO_BIN_EXE_multirep"))
+        .args(["-R", "-i", "-r", "Hana=Sora", "--exclude", "sub"])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!("Sora", read("b.txt"));
+    assert_eq!("Sora", read("vendor/f.rs"));
+    assert_eq!("Minami", read("sub/c.rs"));
+    assert_eq!("Hana", read("target/d.rs"));
+
+    assert!(!multirep(&["-R", "-r", "a=b"], "").status.success());
+    assert!(
+        !multirep(&["--include", "*.rs", "-i", "-r", "a=b", "x"], "")
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn dry_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let hana = dir.path().join("hana.txt");
+    let other = dir.path().join("other.txt");
+    let text = "Hana is cute\n1\n2\n3\n4\n5\n6\n7\nHana";
+    fs::write(&hana, text).unwrap();
+    fs::write(&other, "Sora is cute\n").unwrap();
+    let in_dir = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_multirep"))
+            .args(args)
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        format!("{}\n", path(&hana)),
+        stdout(&["-n", "-r", "Hana=Minami", path(&hana), path(&other)], "")
+    );
+    let diff = "--- a/hana.txt\n+++ b/hana.txt\n\
+                @@ -1,2 +1,2 @@\n-Hana is cute\n+Minami is cute\n 1\n\
+                @@ -8,2 +8,2 @@\n 7\n-Hana\n\\ No newline at end of file\n\
+                +Minami\n\\ No newline at end of file\n";
+    assert_eq!(
+        diff,
+        in_dir(&["-n", "--diff", "-U1", "-r", "Hana=Minami", "./hana.txt"])
+    );
+    assert_eq!(
+        diff,
+        in_dir(&["-R", "-n", "--diff", "-U1", "-r", "Hana=Minami"])
+    );
+    assert_eq!(text, fs::read_to_string(&hana).unwrap());
+
+    // absolute paths are made relative to the current directory, or to the root
+    let args = [
+        "-R",
+        "-n",
+        "--diff",
+        "-U1",
+        "-r",
+        "Hana=Minami",
+        path(dir.path()),
+    ];
+    assert_eq!(diff, in_dir(&args));
+    let absolute = path(dir.path()).trim_start_matches('/');
+    assert!(stdout(&args, "").starts_with(&format!(
+        "--- a/{absolute}/hana.txt\n+++ b/{absolute}/hana.txt\n"
+    )));
+
+    assert!(!multirep(&["--diff", "-r", "a=b", path(&hana)], "")
+        .status
+        .success());
+}
+
+#[test]
+fn interactive() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = dir.path().join("first.txt");
+    let second = dir.path().join("second.txt");
+    let text = "Hana is cute\nHana is cool\nSora is cute\nHana is kind\n";
+    fs::write(&first, text).unwrap();
+    fs::write(&second, "Hana\n").unwrap();
+    let read = |path: &Path| fs::read_to_string(path).unwrap();
+    let args = [
+        "-i",
+        "--interactive",
+        "-U1",
+        "-r",
+        "Hana=Minami",
+        "-r",
+        "Sora=Hana",
+    ];
+
+    let files = [&args[..], &[path(&first), path(&second)]].concat();
+
+    // skip, accept all for the pattern, then skip after a wrong answer
+    let output = stdout(&files, "n\na\nx\nn\n");
+    assert_eq!(
+        "Hana is cute\nMinami is cool\nSora is cute\nMinami is kind\n",
+        read(&first)
+    );
+    assert_eq!("Minami\n", read(&second));
+    assert!(output.contains("@@ -1,2 +1,2 @@\n-Hana is cute\n+Minami is cute\n Hana is cool\n"));
+    assert!(output.contains("q - quit"));
+    assert_eq!(
+        2,
+        output.matches("Replace \"Hana\" with \"Minami\"").count()
+    );
+    assert_eq!(2, output.matches("Replace \"Sora\" with \"Hana\"").count());
+
+    // quitting, or running out of answers, keeps what was accepted before
+    for answers in ["y\nq\n", "y\n"] {
+        fs::write(&first, text).unwrap();
+        fs::write(&second, "Hana\n").unwrap();
+        stdout(&files, answers);
+        assert_eq!(
+            "Minami is cute\nHana is cool\nSora is cute\nHana is kind\n",
+            read(&first)
+        );
+        assert_eq!("Hana\n", read(&second));
+    }
+
+    fs::write(&first, "Hana loves Minami\n").unwrap();
+    let swap = [
+        "-i",
+        "--interactive",
+        "--swap",
+        "Hana",
+        "Minami",
+        path(&first),
+    ];
+    stdout(&swap, "y\ny\n");
+    assert_eq!("Minami loves Hana\n", read(&first));
+
+    assert!(!multirep(&["--interactive", "-r", "a=b", path(&first)], "")
+        .status
+        .success());
+}